@@ -0,0 +1,292 @@
+//! Record `Alt-Svc` alternative service advertisements.
+//!
+//! Servers use the `Alt-Svc` header ([RFC 7838](https://tools.ietf.org/html/rfc7838)) to
+//! advertise other protocols an origin can be reached over, such as HTTP/3. Clients configured
+//! with an `AltSvcCache` record these advertisements so that later connections can act on them.
+
+use crate::{Error, Response};
+
+use http_types::url::Url;
+use http_types::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The `Alt-Svc` header name.
+const ALT_SVC: &str = "alt-svc";
+
+/// How long an alternative is valid for when the server doesn't send `ma`.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A single alternative service advertised in an `Alt-Svc` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltSvc {
+    /// The ALPN protocol id of the alternative, e.g. `h3`, with its percent-encoding undone.
+    pub protocol: String,
+    /// The alternative host, or `None` if it's the same host as the origin.
+    pub host: Option<String>,
+    /// The alternative port.
+    pub port: u16,
+    /// How long the alternative may be used for after it was received.
+    pub max_age: Duration,
+    /// Whether the alternative should survive network configuration changes.
+    pub persist: bool,
+}
+
+impl AltSvc {
+    /// Parse the value of an `Alt-Svc` header.
+    ///
+    /// The special value `clear` yields no alternatives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http_client::alt_svc::AltSvc;
+    ///
+    /// let alts = AltSvc::parse_header(r#"h3=":443"; ma=3600"#).unwrap();
+    /// assert_eq!(alts[0].protocol, "h3");
+    /// assert_eq!(alts[0].port, 443);
+    /// ```
+    pub fn parse_header(value: &str) -> Result<Vec<AltSvc>, Error> {
+        if value.trim() == "clear" {
+            return Ok(Vec::new());
+        }
+
+        split_outside_quotes(value, ',')
+            .into_iter()
+            .filter(|alt| !alt.trim().is_empty())
+            .map(AltSvc::parse_one)
+            .collect()
+    }
+
+    fn parse_one(value: &str) -> Result<AltSvc, Error> {
+        let mut parts = split_outside_quotes(value, ';').into_iter();
+        // UNWRAP: splitting always yields at least one part.
+        let (protocol, authority) = parse_pair(parts.next().unwrap())?;
+        let authority = authority.trim_matches('"');
+        let colon = authority
+            .rfind(':')
+            .ok_or_else(|| invalid("missing port"))?;
+        let host = &authority[..colon];
+        let port = authority[colon + 1..]
+            .parse()
+            .map_err(|_| invalid("invalid port"))?;
+
+        let mut alt = AltSvc {
+            protocol: percent_decode(protocol)?,
+            host: if host.is_empty() {
+                None
+            } else {
+                Some(host.to_string())
+            },
+            port,
+            max_age: DEFAULT_MAX_AGE,
+            persist: false,
+        };
+
+        for param in parts {
+            let (name, value) = parse_pair(param)?;
+            let value = value.trim_matches('"');
+            match name {
+                "ma" => {
+                    let secs = value.parse().map_err(|_| invalid("invalid ma"))?;
+                    alt.max_age = Duration::from_secs(secs);
+                }
+                "persist" => alt.persist = value == "1",
+                // Unknown parameters must be ignored.
+                _ => {}
+            }
+        }
+
+        Ok(alt)
+    }
+}
+
+/// A record of the alternative services advertised by each origin.
+///
+/// Cloning the cache is cheap, and clones share their entries.
+#[derive(Debug, Clone, Default)]
+pub struct AltSvcCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// The alternatives most recently advertised by an origin.
+#[derive(Debug)]
+struct Entry {
+    received: Instant,
+    alts: Vec<AltSvc>,
+}
+
+impl AltSvcCache {
+    /// Create a new, empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The unexpired alternatives recorded for the origin of `url`.
+    pub fn get(&self, url: &Url) -> Vec<AltSvc> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&url.origin().ascii_serialization()) {
+            Some(entry) => entry
+                .alts
+                .iter()
+                .filter(|alt| entry.received.elapsed() < alt.max_age)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Record the `Alt-Svc` header of a response received from `url`, replacing any
+    /// alternatives previously recorded for its origin.
+    ///
+    /// Responses without the header, or with an invalid one, leave the cache unchanged. So do
+    /// responses received over cleartext `http`, since anyone on the path could have inserted
+    /// the header ([RFC 7838 §9.2](https://tools.ietf.org/html/rfc7838#section-9.2)).
+    pub fn record(&self, url: &Url, res: &Response) {
+        if url.scheme() != "https" {
+            return;
+        }
+        let values = match res.header(ALT_SVC) {
+            Some(values) => values,
+            None => return,
+        };
+        let value = values
+            .iter()
+            .map(|value| value.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        match AltSvc::parse_header(&value) {
+            Ok(alts) => {
+                let origin = url.origin().ascii_serialization();
+                let mut entries = self.entries.lock().unwrap();
+                if alts.is_empty() {
+                    entries.remove(&origin);
+                } else {
+                    let received = Instant::now();
+                    entries.insert(origin, Entry { received, alts });
+                }
+            }
+            Err(err) => log::debug!("ignoring Alt-Svc header from {}: {}", url, err),
+        }
+    }
+}
+
+/// Split `value` on `separator`, ignoring separators inside quoted strings.
+fn split_outside_quotes(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&value[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Undo the percent-encoding of an ALPN protocol id, such as `w%3Dx%3Ay` for `w=x:y`.
+fn percent_decode(value: &str) -> Result<String, Error> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = after
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| invalid("invalid percent-encoding in protocol id"))?;
+            bytes.push(hex);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("protocol id isn't valid UTF-8"))
+}
+
+/// Parse a `name=value` pair.
+fn parse_pair(pair: &str) -> Result<(&str, &str), Error> {
+    let eq = pair
+        .find('=')
+        .ok_or_else(|| invalid("expected name=value"))?;
+    Ok((pair[..eq].trim(), pair[eq + 1..].trim()))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::from_str(
+        StatusCode::BadGateway,
+        format!("invalid Alt-Svc header: {}", msg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_alternatives_and_parameters() -> Result<(), Error> {
+        let alts =
+            AltSvc::parse_header(r#"h3=":443"; ma=3600, h2="alt.example.com:8443"; persist=1"#)?;
+        assert_eq!(
+            alts,
+            [
+                AltSvc {
+                    protocol: "h3".to_string(),
+                    host: None,
+                    port: 443,
+                    max_age: Duration::from_secs(3600),
+                    persist: false,
+                },
+                AltSvc {
+                    protocol: "h2".to_string(),
+                    host: Some("alt.example.com".to_string()),
+                    port: 8443,
+                    max_age: DEFAULT_MAX_AGE,
+                    persist: true,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_protocol_ids() -> Result<(), Error> {
+        let alts = AltSvc::parse_header(r#"w%3Dx%3Ay=":443""#)?;
+        assert_eq!(alts[0].protocol, "w=x:y");
+        assert!(AltSvc::parse_header(r#"h%3=":443""#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_cleartext_responses() {
+        let cache = AltSvcCache::new();
+        let url = Url::parse("http://example.com/").unwrap();
+
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header(ALT_SVC, r#"h3=":443""#);
+        cache.record(&url, &res);
+        assert!(cache.get(&url).is_empty());
+    }
+
+    #[test]
+    fn clear_removes_recorded_alternatives() {
+        let cache = AltSvcCache::new();
+        let url = Url::parse("https://example.com/").unwrap();
+
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header(ALT_SVC, r#"h3=":443""#);
+        cache.record(&url, &res);
+        assert_eq!(cache.get(&url).len(), 1);
+
+        res.insert_header(ALT_SVC, "clear");
+        cache.record(&url, &res);
+        assert!(cache.get(&url).is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+//! Spread requests across several fixed origins.
+
+use crate::{Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use http_types::url::Url;
+use http_types::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How a `BalancedClient` picks the target for each request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Cycle through the targets, visiting each in proportion to its weight and interleaving
+    /// them as evenly as possible.
+    WeightedRoundRobin,
+    /// Pick a target at random, with probability in proportion to its weight.
+    Random,
+}
+
+/// A client which sends each request to one of several targets, replacing the scheme, host and
+/// port of its URL with the target's while keeping its path and query.
+///
+/// A target which fails several requests in a row, by returning an error rather than a
+/// response, is ejected for a cooldown period, after which it's tried again. If every target
+/// is ejected, requests are spread across all of them rather than failing outright.
+///
+/// By default targets are picked with [`Strategy::WeightedRoundRobin`], and ejected for 30
+/// seconds after 3 consecutive failures.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::balance::{BalancedClient, Strategy};
+/// use http_client::h1::H1Client;
+/// use http_types::Url;
+///
+/// let targets = vec![
+///     (Url::parse("http://10.0.0.1:8080").unwrap(), 3),
+///     (Url::parse("http://10.0.0.2:8080").unwrap(), 1),
+/// ];
+/// let client = BalancedClient::new(H1Client::new(), targets).with_strategy(Strategy::Random);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BalancedClient<C> {
+    client: Arc<C>,
+    targets: Arc<Mutex<Vec<Target>>>,
+    strategy: Strategy,
+    max_failures: u32,
+    cooldown: Duration,
+    random: Arc<Random>,
+}
+
+#[derive(Debug)]
+struct Target {
+    url: Url,
+    weight: u32,
+    /// The smooth weighted round-robin counter.
+    current: i64,
+    /// Consecutive failed requests.
+    failures: u32,
+    ejected_until: Option<Instant>,
+}
+
+impl<C: HttpClient> BalancedClient<C> {
+    /// Wrap `client`, spreading requests across `targets` according to their weights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `targets` is empty, a weight is zero, or a URL has no host.
+    pub fn new(client: C, targets: Vec<(Url, u32)>) -> Self {
+        assert!(!targets.is_empty(), "a balanced client needs a target");
+        let targets = targets
+            .into_iter()
+            .map(|(url, weight)| {
+                assert!(weight > 0, "the weight of {} must be at least 1", url);
+                assert!(url.has_host(), "target {} has no host", url);
+                Target {
+                    url,
+                    weight,
+                    current: 0,
+                    failures: 0,
+                    ejected_until: None,
+                }
+            })
+            .collect();
+
+        Self {
+            client: Arc::new(client),
+            targets: Arc::new(Mutex::new(targets)),
+            strategy: Strategy::WeightedRoundRobin,
+            max_failures: 3,
+            cooldown: Duration::from_secs(30),
+            random: Arc::new(Random::new()),
+        }
+    }
+
+    /// Pick targets with `strategy`.
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Eject a target for `cooldown` once it fails `max_failures` requests in a row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_failures` is zero.
+    pub fn with_ejection(mut self, max_failures: u32, cooldown: Duration) -> Self {
+        assert!(
+            max_failures > 0,
+            "targets must be allowed at least 1 failure"
+        );
+        self.max_failures = max_failures;
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Whether the target at `url` is currently ejected.
+    pub fn is_ejected(&self, url: &Url) -> bool {
+        let now = Instant::now();
+        self.targets.lock().unwrap().iter().any(|target| {
+            &target.url == url && matches!(target.ejected_until, Some(until) if until > now)
+        })
+    }
+
+    /// Pick a target, returning its index and URL.
+    fn pick(&self) -> (usize, Url) {
+        let now = Instant::now();
+        let mut targets = self.targets.lock().unwrap();
+        for target in targets.iter_mut() {
+            if matches!(target.ejected_until, Some(until) if until <= now) {
+                target.ejected_until = None;
+            }
+        }
+
+        let mut candidates: Vec<usize> = (0..targets.len())
+            .filter(|&i| targets[i].ejected_until.is_none())
+            .collect();
+        if candidates.is_empty() {
+            candidates = (0..targets.len()).collect();
+        }
+        let total: i64 = candidates
+            .iter()
+            .map(|&i| i64::from(targets[i].weight))
+            .sum();
+
+        let chosen = match self.strategy {
+            Strategy::WeightedRoundRobin => {
+                let mut chosen = candidates[0];
+                for &i in &candidates {
+                    targets[i].current += i64::from(targets[i].weight);
+                    if targets[i].current > targets[chosen].current {
+                        chosen = i;
+                    }
+                }
+                targets[chosen].current -= total;
+                chosen
+            }
+            Strategy::Random => {
+                let mut point = (self.random.next() % total as u64) as i64;
+                let mut chosen = candidates[0];
+                for &i in &candidates {
+                    point -= i64::from(targets[i].weight);
+                    if point < 0 {
+                        chosen = i;
+                        break;
+                    }
+                }
+                chosen
+            }
+        };
+        (chosen, targets[chosen].url.clone())
+    }
+}
+
+impl<C: HttpClient> HttpClient for BalancedClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let (index, target) = self.pick();
+        let client = self.client.clone();
+        let targets = self.targets.clone();
+        let max_failures = self.max_failures;
+        let cooldown = self.cooldown;
+        Box::pin(async move {
+            retarget(req.url_mut(), &target)?;
+            let res = client.send(req).await;
+
+            let mut targets = targets.lock().unwrap();
+            let target = &mut targets[index];
+            if res.is_ok() {
+                target.failures = 0;
+            } else {
+                target.failures += 1;
+                if target.failures >= max_failures {
+                    log::debug!("ejecting {} after {} failures", target.url, target.failures);
+                    target.failures = 0;
+                    target.ejected_until = Some(Instant::now() + cooldown);
+                }
+            }
+            res
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// Point `url` at the scheme, host and port of `target`.
+fn retarget(url: &mut Url, target: &Url) -> Result<(), Error> {
+    let invalid = || {
+        Error::from_str(
+            StatusCode::BadRequest,
+            format!("cannot send {} to target {}", url, target),
+        )
+    };
+    let mut retargeted = url.clone();
+    retargeted
+        .set_scheme(target.scheme())
+        .map_err(|_| invalid())?;
+    retargeted
+        .set_host(target.host_str())
+        .map_err(|_| invalid())?;
+    retargeted.set_port(target.port()).map_err(|_| invalid())?;
+    *url = retargeted;
+    Ok(())
+}
+
+/// A cheap source of random numbers for picking targets, which needn't be unpredictable.
+#[derive(Debug)]
+struct Random {
+    seed: RandomState,
+    counter: AtomicU64,
+}
+
+impl Random {
+    fn new() -> Self {
+        Self {
+            seed: RandomState::new(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn next(&self) -> u64 {
+        let mut hasher = self.seed.build_hasher();
+        hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use http_types::Method;
+    use std::collections::HashMap;
+
+    /// Records the host of every request, failing those sent to `bad.example`.
+    #[derive(Debug, Clone, Default)]
+    struct Recorder {
+        hosts: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl HttpClient for Recorder {
+        fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let host = req.url().host_str().unwrap().to_string();
+            assert_eq!(req.url().path(), "/search");
+            assert_eq!(req.url().query(), Some("q=1"));
+            self.hosts.lock().unwrap().push(host.clone());
+            Box::pin(async move {
+                if host == "bad.example" {
+                    Err(Error::from_str(
+                        StatusCode::BadGateway,
+                        "connection refused",
+                    ))
+                } else {
+                    Ok(Response::new(StatusCode::Ok))
+                }
+            })
+        }
+    }
+
+    fn request() -> Request {
+        Request::new(Method::Get, "http://placeholder/search?q=1")
+    }
+
+    #[async_std::test]
+    async fn distributes_by_weight() -> Result<(), Error> {
+        for strategy in &[Strategy::WeightedRoundRobin, Strategy::Random] {
+            let recorder = Recorder::default();
+            let targets = vec![
+                (Url::parse("https://a.example").unwrap(), 3),
+                (Url::parse("http://b.example:8080").unwrap(), 1),
+            ];
+            let client = BalancedClient::new(recorder.clone(), targets).with_strategy(*strategy);
+            for _ in 0..400 {
+                client.send(request()).await?;
+            }
+
+            let mut counts = HashMap::new();
+            for host in recorder.hosts.lock().unwrap().iter() {
+                *counts.entry(host.clone()).or_insert(0) += 1;
+            }
+            match strategy {
+                Strategy::WeightedRoundRobin => {
+                    assert_eq!(counts["a.example"], 300);
+                    assert_eq!(counts["b.example"], 100);
+                }
+                Strategy::Random => assert!((60..140).contains(&counts["b.example"])),
+            }
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn ejects_and_reinstates_failing_targets() -> Result<(), Error> {
+        let recorder = Recorder::default();
+        let good = Url::parse("http://good.example").unwrap();
+        let bad = Url::parse("http://bad.example").unwrap();
+        let client = BalancedClient::new(recorder.clone(), vec![(good, 1), (bad.clone(), 1)])
+            .with_ejection(2, Duration::from_millis(100));
+
+        // Alternating targets, the bad one fails twice in the first four requests.
+        for _ in 0..4 {
+            let _ = client.send(request()).await;
+        }
+        assert!(client.is_ejected(&bad));
+        for _ in 0..4 {
+            client.send(request()).await?;
+        }
+        let hosts = recorder.hosts.lock().unwrap().clone();
+        assert!(hosts[4..].iter().all(|host| host == "good.example"));
+
+        task::sleep(Duration::from_millis(150)).await;
+        assert!(!client.is_ejected(&bad));
+        for _ in 0..2 {
+            let _ = client.send(request()).await;
+        }
+        assert!(recorder.hosts.lock().unwrap()[8..].contains(&"bad.example".to_string()));
+        Ok(())
+    }
+}
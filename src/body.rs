@@ -0,0 +1,156 @@
+//! Request bodies fed from another task.
+
+use crate::{Body, Error};
+
+use futures::channel::mpsc;
+use futures::io::{AsyncRead, BufReader};
+use futures::{SinkExt, TryStreamExt};
+use http_types::StatusCode;
+use std::io;
+
+/// How many chunks may wait in the channel before `BodySender::send` waits for the body to be
+/// read.
+const CHANNEL_CAPACITY: usize = 8;
+
+/// Create a body which streams the chunks sent through the returned `BodySender`.
+///
+/// The body has no known length, so it's sent with chunked encoding. It ends once the sender is
+/// dropped, and fails if the sender calls [`abort`](BodySender::abort), which fails the request
+/// sending it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[async_std::main]
+/// # async fn main() -> Result<(), http_client::Error> {
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::{body, HttpClient};
+/// use http_types::{Method, Request};
+///
+/// let (mut sender, body) = body::channel();
+/// async_std::task::spawn(async move {
+///     sender.send("first chunk").await?;
+///     sender.send("second chunk").await?;
+///     http_types::Result::Ok(())
+/// });
+///
+/// let mut req = Request::new(Method::Post, "http://example.com/upload");
+/// req.set_body(body);
+/// let res = H1Client::new().send(req).await?;
+/// # }
+/// # Ok(()) }
+/// ```
+pub fn channel() -> (BodySender, Body) {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let reader = BufReader::new(rx.into_async_read());
+    (BodySender { tx }, Body::from_reader(reader, None))
+}
+
+/// Wrap a response body stream, as the backends do for the bodies they return.
+#[cfg_attr(
+    not(any(feature = "curl_client", feature = "test-util")),
+    allow(dead_code)
+)]
+pub(crate) fn from_reader<R>(reader: R, len: Option<usize>) -> Body
+where
+    R: AsyncRead + Unpin + Send + Sync + 'static,
+{
+    Body::from_reader(BufReader::new(reader), len)
+}
+
+/// The sending half of a body created with [`channel`].
+#[derive(Debug)]
+pub struct BodySender {
+    tx: mpsc::Sender<io::Result<Vec<u8>>>,
+}
+
+impl BodySender {
+    /// Append `chunk` to the body, waiting while the channel is full.
+    ///
+    /// Fails once the body has been dropped, such as when the request failed.
+    pub async fn send(&mut self, chunk: impl Into<Vec<u8>>) -> Result<(), Error> {
+        self.tx
+            .send(Ok(chunk.into()))
+            .await
+            .map_err(|_| Error::from_str(StatusCode::InternalServerError, "body was dropped"))
+    }
+
+    /// End the body with `error` instead of letting it finish, aborting the upload.
+    ///
+    /// The request fails with an `io::Error` wrapping `error`, which can be recovered with
+    /// `err.downcast_ref::<std::io::Error>()` and then `get_ref`.
+    pub async fn abort<E>(mut self, error: E)
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let error = io::Error::other(error);
+        // Nothing is left to abort if the body was already dropped.
+        let _ = self.tx.send(Err(error)).await;
+    }
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use crate::{HttpClient, Request};
+    use async_std::io::ReadExt;
+    use async_std::task;
+    use http_types::url::Url;
+    use http_types::Method;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct ProducerFailed;
+
+    impl fmt::Display for ProducerFailed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("producer failed")
+        }
+    }
+
+    impl std::error::Error for ProducerFailed {}
+
+    #[async_std::test]
+    async fn abort_fails_the_request() -> http_types::Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Record everything the client sends until it closes the connection.
+        let server = task::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await?;
+            http_types::Result::Ok(String::from_utf8(received)?)
+        });
+
+        let (mut sender, body) = channel();
+        let producer = task::spawn(async move {
+            sender.send("first,").await?;
+            sender.send("second").await?;
+            sender.abort(ProducerFailed).await;
+            http_types::Result::Ok(())
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let mut req = Request::new(Method::Post, url);
+        req.set_body(body);
+        let err = H1Client::new().send(req).await.unwrap_err();
+        producer.await?;
+
+        let source = err
+            .downcast_ref::<io::Error>()
+            .and_then(|err| err.get_ref())
+            .unwrap();
+        assert!(source.is::<ProducerFailed>());
+
+        // Both chunks arrived, but not the chunk which ends the body.
+        let received = server.await?;
+        assert!(received.contains("first,"));
+        assert!(received.contains("second"));
+        assert!(!received.ends_with("0\r\n\r\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! Drop the bodies of requests whose method doesn't expect one.
+
+use crate::Request;
+
+use http_types::headers::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http_types::Method;
+
+/// Whether requests with `method` shouldn't carry a body.
+///
+/// `TRACE` requests must not carry a body, and the others give it no defined semantics, so many
+/// servers reject it.
+fn is_bodyless(method: Method) -> bool {
+    matches!(
+        method,
+        Method::Get
+            | Method::Head
+            | Method::Delete
+            | Method::Options
+            | Method::Trace
+            | Method::Connect
+    )
+}
+
+/// Remove the body and its framing headers from `req` if its method shouldn't carry one.
+// Unused when no backend is enabled.
+#[allow(dead_code)]
+pub(crate) fn strip_body(req: &mut Request) {
+    if is_bodyless(req.method()) {
+        req.take_body();
+        req.remove_header(CONTENT_LENGTH);
+        req.remove_header(TRANSFER_ENCODING);
+    }
+}
@@ -0,0 +1,185 @@
+//! Responses with their body read into memory.
+
+use crate::{Body, Error, Response};
+
+use futures::io::AsyncReadExt;
+use http_types::headers::{self, HeaderName, HeaderValues};
+use http_types::{Mime, StatusCode, Version};
+
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+
+/// A response whose body has been read into memory, so it can be read any number of times.
+///
+/// The response's status and headers are available through accessors, and its body through
+/// `body` and the methods decoding it. Get a complete `Response` back with `into_response`.
+///
+/// # Examples
+///
+/// ```
+/// # async_std::task::block_on(async {
+/// use http_client::{BufferedResponse, Response};
+/// use http_types::StatusCode;
+///
+/// let mut res = Response::new(StatusCode::Ok);
+/// res.set_body("hello");
+///
+/// let res = BufferedResponse::new(res, 1024).await.unwrap();
+/// assert_eq!(res.status(), StatusCode::Ok);
+/// assert_eq!(res.body_string().unwrap(), "hello");
+/// assert_eq!(res.body(), b"hello");
+/// # })
+/// ```
+#[derive(Debug)]
+pub struct BufferedResponse {
+    res: Response,
+    body: Vec<u8>,
+}
+
+impl BufferedResponse {
+    /// Read the body of `res` into memory.
+    ///
+    /// Fails with a `413 Payload Too Large` error if the body is longer than `max_len` bytes,
+    /// without reading more than `max_len + 1` of them.
+    pub async fn new(mut res: Response, max_len: usize) -> Result<Self, Error> {
+        let too_large = || {
+            Error::from_str(
+                StatusCode::PayloadTooLarge,
+                format!("response body longer than {} bytes", max_len),
+            )
+        };
+        if matches!(res.len(), Some(len) if len > max_len) {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::new();
+        res.take_body()
+            .take(max_len as u64 + 1)
+            .read_to_end(&mut body)
+            .await?;
+        if body.len() > max_len {
+            return Err(too_large());
+        }
+        Ok(Self { res, body })
+    }
+
+    /// The status code.
+    pub fn status(&self) -> StatusCode {
+        self.res.status()
+    }
+
+    /// The HTTP version, if known.
+    pub fn version(&self) -> Option<Version> {
+        self.res.version()
+    }
+
+    /// The values of the header `name`, if present.
+    pub fn header(&self, name: impl Into<HeaderName>) -> Option<&HeaderValues> {
+        self.res.header(name)
+    }
+
+    /// An iterator over the headers.
+    pub fn iter(&self) -> headers::Iter<'_> {
+        self.res.iter()
+    }
+
+    /// The content type, from the `Content-Type` header.
+    pub fn content_type(&self) -> Option<Mime> {
+        self.res.content_type()
+    }
+
+    /// The body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The body as a string.
+    ///
+    /// Fails with a `422 Unprocessable Entity` error if the body isn't valid UTF-8.
+    pub fn body_string(&self) -> Result<String, Error> {
+        String::from_utf8(self.body.clone())
+            .map_err(|err| Error::new(StatusCode::UnprocessableEntity, err))
+    }
+
+    /// The body deserialized from JSON.
+    ///
+    /// Fails with a `422 Unprocessable Entity` error if the body isn't valid JSON for `T`.
+    #[cfg_attr(feature = "docs", doc(cfg(json)))]
+    #[cfg(feature = "json")]
+    pub fn body_json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.body)
+            .map_err(|err| Error::new(StatusCode::UnprocessableEntity, err))
+    }
+
+    /// Turn this back into a `Response`, with the buffered body as its body.
+    pub fn into_response(self) -> Response {
+        let mut res = self.res;
+        res.set_body(Body::from(self.body));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn body_can_be_read_repeatedly() -> Result<(), Error> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header("Content-Type", "text/plain");
+        res.set_body(Body::from_reader(
+            futures::io::Cursor::new(b"hello".to_vec()),
+            None,
+        ));
+
+        let res = BufferedResponse::new(res, 5).await?;
+        assert_eq!(res.body_string()?, "hello");
+        assert_eq!(res.body_string()?, "hello");
+        assert_eq!(res.header("Content-Type").unwrap(), "text/plain");
+
+        let mut res = res.into_response();
+        assert_eq!(res.len(), Some(5));
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[async_std::test]
+    async fn body_json_can_be_read_repeatedly() -> Result<(), Error> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body("[1, 2]");
+
+        let res = BufferedResponse::new(res, 1024).await?;
+        assert_eq!(res.body_json::<Vec<u32>>()?, [1, 2]);
+        assert_eq!(res.body_json::<Vec<u32>>()?, [1, 2]);
+        assert!(res.body_json::<String>().is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn body_longer_than_max_len_is_an_error() {
+        // Without a known length, the limit is only found by reading.
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(Body::from_reader(
+            futures::io::Cursor::new(b"hello".to_vec()),
+            None,
+        ));
+        let err = BufferedResponse::new(res, 4).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PayloadTooLarge);
+
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body("hello");
+        let err = BufferedResponse::new(res, 4).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PayloadTooLarge);
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error() {
+        let res = BufferedResponse {
+            res: Response::new(StatusCode::Ok),
+            body: vec![0xff],
+        };
+        let err = res.body_string().unwrap_err();
+        assert_eq!(err.status(), StatusCode::UnprocessableEntity);
+    }
+}
@@ -0,0 +1,175 @@
+//! Build requests in a single expression.
+//!
+//! # Examples
+//!
+//! ```
+//! use http_client::builder;
+//!
+//! let req = builder::post("https://example.com/search")
+//!     .query(&[("q", "http client")])
+//!     .header("Accept", "application/json")
+//!     .body("hello")
+//!     .build();
+//! assert_eq!(req.url().as_str(), "https://example.com/search?q=http+client");
+//! ```
+
+use crate::{Body, Request};
+
+use http_types::headers::{HeaderName, ToHeaderValues};
+use http_types::url::Url;
+use http_types::Method;
+use std::convert::TryInto;
+use std::fmt::Debug;
+
+/// A builder for a `Request`.
+#[derive(Debug)]
+pub struct RequestBuilder {
+    req: Request,
+}
+
+impl RequestBuilder {
+    /// Start building a request with the given method and URL.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `url` is not a valid URL, like `Request::new`.
+    pub fn new<U>(method: Method, url: U) -> Self
+    where
+        U: TryInto<Url>,
+        U::Error: Debug,
+    {
+        Self {
+            req: Request::new(method, url),
+        }
+    }
+
+    /// Append a header value.
+    pub fn header(mut self, name: impl Into<HeaderName>, values: impl ToHeaderValues) -> Self {
+        self.req.append_header(name, values);
+        self
+    }
+
+    /// Append query parameters to the URL, percent-encoding them as needed.
+    pub fn query<K, V>(mut self, pairs: &[(K, V)]) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        if !pairs.is_empty() {
+            self.req
+                .url_mut()
+                .query_pairs_mut()
+                .extend_pairs(pairs.iter().map(|(k, v)| (k.as_ref(), v.as_ref())));
+        }
+        self
+    }
+
+    /// Append the fields of `value` to the URL as query parameters.
+    ///
+    /// See [`append_query`](crate::query::append_query) for how `value` is serialized.
+    #[cfg_attr(feature = "docs", doc(cfg(query)))]
+    #[cfg(feature = "query")]
+    pub fn query_struct<T>(mut self, value: &T) -> Result<Self, crate::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        crate::query::append_query(&mut self.req, value)?;
+        Ok(self)
+    }
+
+    /// Set the body.
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.req.set_body(body);
+        self
+    }
+
+    /// Finish building the request.
+    pub fn build(self) -> Request {
+        self.req
+    }
+}
+
+impl From<RequestBuilder> for Request {
+    fn from(builder: RequestBuilder) -> Request {
+        builder.build()
+    }
+}
+
+macro_rules! method {
+    ($(#[$doc:meta])* $name:ident, $method:ident) => {
+        $(#[$doc])*
+        ///
+        /// # Panics
+        ///
+        /// Panics if `url` is not a valid URL.
+        pub fn $name<U>(url: U) -> RequestBuilder
+        where
+            U: TryInto<Url>,
+            U::Error: Debug,
+        {
+            RequestBuilder::new(Method::$method, url)
+        }
+    };
+}
+
+method!(
+    /// Start building a `GET` request.
+    get,
+    Get
+);
+method!(
+    /// Start building a `HEAD` request.
+    head,
+    Head
+);
+method!(
+    /// Start building a `POST` request.
+    post,
+    Post
+);
+method!(
+    /// Start building a `PUT` request.
+    put,
+    Put
+);
+method!(
+    /// Start building a `DELETE` request.
+    delete,
+    Delete
+);
+method!(
+    /// Start building a `PATCH` request.
+    patch,
+    Patch
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn builds_request() -> http_types::Result<()> {
+        let mut req = post("http://example.com/search?a=1")
+            .query(&[("q", "rust lang"), ("x", "a&b=c")])
+            .header("X-Test", "1")
+            .header("X-Test", "2")
+            .body("hello")
+            .build();
+
+        assert_eq!(req.method(), Method::Post);
+        assert_eq!(
+            req.url().as_str(),
+            "http://example.com/search?a=1&q=rust+lang&x=a%26b%3Dc"
+        );
+        let values: Vec<_> = req["X-Test"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(values, ["1", "2"]);
+        assert_eq!(req.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn empty_query_leaves_url_unchanged() {
+        let req = get("http://example.com/").query::<&str, &str>(&[]).build();
+        assert_eq!(req.url().as_str(), "http://example.com/");
+    }
+}
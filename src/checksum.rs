@@ -0,0 +1,211 @@
+//! Compute a digest over a response body while it streams.
+
+use crate::{Body, Error};
+
+use digest::{Digest, Output};
+use futures::channel::oneshot;
+use futures::io::AsyncRead;
+use futures::ready;
+use http_types::StatusCode;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An `AsyncRead` over a response body which feeds every byte read into a digest `D`.
+///
+/// Created by `ResponseExt::with_digest` and `ResponseExt::verify_digest`.
+pub struct DigestReader<D: Digest> {
+    body: Body,
+    digest: Option<D>,
+    expected: Option<Vec<u8>>,
+    sender: Option<oneshot::Sender<Output<D>>>,
+    /// Whether the body didn't match `expected`, so every later read fails too.
+    mismatch: bool,
+}
+
+impl<D: Digest> DigestReader<D> {
+    pub(crate) fn new(body: Body) -> (Self, DigestOutput<D>) {
+        let (sender, receiver) = oneshot::channel();
+        let reader = Self {
+            body,
+            digest: Some(D::new()),
+            expected: None,
+            sender: Some(sender),
+            mismatch: false,
+        };
+        (reader, DigestOutput { receiver })
+    }
+
+    pub(crate) fn verify(body: Body, expected: Vec<u8>) -> Self {
+        Self {
+            body,
+            digest: Some(D::new()),
+            expected: Some(expected),
+            sender: None,
+            mismatch: false,
+        }
+    }
+}
+
+impl<D: Digest> fmt::Debug for DigestReader<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestReader")
+            .field("body", &self.body)
+            .field("finished", &self.digest.is_none())
+            .finish()
+    }
+}
+
+impl<D: Digest + Unpin> AsyncRead for DigestReader<D> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.mismatch {
+            return Poll::Ready(Err(mismatch()));
+        }
+        let read = ready!(Pin::new(&mut this.body).poll_read(cx, buf))?;
+        if read > 0 {
+            if let Some(digest) = this.digest.as_mut() {
+                digest.update(&buf[..read]);
+            }
+        } else if !buf.is_empty() {
+            // End of body: the digest is complete.
+            if let Some(digest) = this.digest.take() {
+                let output = digest.finalize();
+                if let Some(expected) = &this.expected {
+                    if output.as_slice() != expected.as_slice() {
+                        this.mismatch = true;
+                        return Poll::Ready(Err(mismatch()));
+                    }
+                }
+                if let Some(sender) = this.sender.take() {
+                    let _ = sender.send(output);
+                }
+            }
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+fn mismatch() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "response body digest mismatch")
+}
+
+/// The digest of a body read through a `DigestReader`.
+///
+/// Resolves once the reader reaches the end of the body, or fails if the reader is dropped first.
+pub struct DigestOutput<D: Digest> {
+    receiver: oneshot::Receiver<Output<D>>,
+}
+
+impl<D: Digest> fmt::Debug for DigestOutput<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestOutput").finish()
+    }
+}
+
+impl<D: Digest> Future for DigestOutput<D> {
+    type Output = Result<Output<D>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let output = ready!(Pin::new(&mut self.receiver).poll(cx));
+        Poll::Ready(output.map_err(|_| {
+            Error::from_str(
+                StatusCode::InternalServerError,
+                "response body was dropped before it was fully read",
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Response, ResponseExt};
+    use futures::io::AsyncReadExt;
+    use http_types::StatusCode;
+    use sha2::Sha256;
+
+    const HELLO_WORLD_SHA256: &str =
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn hello_world() -> Response {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body("hello world");
+        res
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[async_std::test]
+    async fn with_digest_computes_sha256() -> http_types::Result<()> {
+        let (mut reader, digest) = hello_world().with_digest::<Sha256>();
+        let mut body = String::new();
+        reader.read_to_string(&mut body).await?;
+
+        assert_eq!(body, "hello world");
+        assert_eq!(hex(&digest.await?), HELLO_WORLD_SHA256);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn verify_digest_rejects_mismatch() {
+        let mut reader = hello_world().verify_digest::<Sha256>(vec![0; 32]);
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // Reading again must not report a clean end of the body.
+        let err = reader.read(&mut [0; 16]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "h1_client")]
+    #[async_std::test]
+    async fn verifies_downloaded_body() -> http_types::Result<()> {
+        use crate::h1::H1Client;
+        use crate::{HttpClient, Request};
+        use async_std::prelude::FutureExt as _;
+        use async_std::task;
+        use http_types::url::Url;
+        use http_types::Method;
+        use std::time::Duration;
+
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/artifact").get(|_| async { Ok("hello world") });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/artifact", port)).unwrap();
+            let client = H1Client::new();
+
+            let res = client.send(Request::new(Method::Get, url.clone())).await?;
+            let (mut reader, digest) = res.with_digest::<Sha256>();
+            let mut body = String::new();
+            reader.read_to_string(&mut body).await?;
+            assert_eq!(body, "hello world");
+            assert_eq!(hex(&digest.await?), HELLO_WORLD_SHA256);
+
+            let res = client.send(Request::new(Method::Get, url)).await?;
+            let mut reader = res.verify_digest::<Sha256>(vec![0; 32]);
+            let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,575 @@
+//! HTTP Digest access authentication ([RFC 7616](https://www.rfc-editor.org/rfc/rfc7616)).
+
+use crate::replay::{clone_request, take_body};
+use crate::{Body, Capabilities, Error, HttpClient, Request, Response};
+
+use digest::Digest;
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use http_types::headers::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use http_types::StatusCode;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+/// A client which answers `401 Unauthorized` responses carrying a `WWW-Authenticate: Digest`
+/// challenge, retrying the request once with an `Authorization` header.
+///
+/// The `MD5` and `SHA-256` algorithms are supported, with `qop=auth` or without a `qop`. The
+/// most recent challenge for each origin and realm is remembered, so later requests to the same
+/// origin are authorized up front with an increasing nonce count, and only need a round trip
+/// when the server issues a new nonce. Requests to any other origin aren't sent the credentials
+/// until that origin challenges them itself.
+///
+/// Request bodies are read into memory before sending, so a request can be answered again with
+/// the same body when its first attempt is challenged.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::digest_auth::DigestAuthClient;
+/// use http_client::h1::H1Client;
+///
+/// let client = DigestAuthClient::new(H1Client::new(), "user", "secret");
+/// # }
+/// ```
+pub struct DigestAuthClient<C> {
+    client: Arc<C>,
+    credentials: Arc<Credentials>,
+}
+
+struct Credentials {
+    username: String,
+    password: String,
+    /// The last challenge from each origin and realm, most recent last.
+    challenges: Mutex<Vec<Cached>>,
+}
+
+/// A challenge, the origin which issued it, and the nonce count last used with it.
+struct Cached {
+    origin: String,
+    challenge: Challenge,
+    nc: u32,
+}
+
+impl<C> fmt::Debug for DigestAuthClient<C>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestAuthClient")
+            .field("client", &self.client)
+            .field("username", &self.credentials.username)
+            .finish()
+    }
+}
+
+impl<C: HttpClient> DigestAuthClient<C> {
+    /// Wrap `client`, authenticating as `username` with `password`.
+    pub fn new(client: C, username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            client: Arc::new(client),
+            credentials: Arc::new(Credentials {
+                username: username.into(),
+                password: password.into(),
+                challenges: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for DigestAuthClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let client = self.client.clone();
+        let credentials = self.credentials.clone();
+        Box::pin(async move {
+            let body = take_body(&mut req).await?;
+            let retry = clone_request(&req);
+            if let Some(body) = &body {
+                req.set_body(Body::from(body.clone()));
+            }
+
+            // Opaque origins are all serialized as "null", so they can't tell servers apart.
+            let origin = req.url().origin();
+            let origin = origin.is_tuple().then(|| origin.ascii_serialization());
+            if let Some(origin) = &origin {
+                let mut challenges = credentials.challenges.lock().await;
+                if let Some(cached) = challenges.iter_mut().rev().find(|c| c.origin == *origin) {
+                    cached.nc += 1;
+                    authorize(&mut req, &credentials, &cached.challenge, cached.nc)?;
+                }
+            }
+            let res = client.send(req).await?;
+            if res.status() != StatusCode::Unauthorized {
+                return Ok(res);
+            }
+
+            let challenge = match Challenge::from_response(&res) {
+                Some(challenge) => challenge,
+                None => return Ok(res),
+            };
+            let mut req = retry;
+            if let Some(body) = body {
+                req.set_body(Body::from(body));
+            }
+            authorize(&mut req, &credentials, &challenge, 1)?;
+            if let Some(origin) = origin {
+                let mut challenges = credentials.challenges.lock().await;
+                challenges.retain(|c| c.origin != origin || c.challenge.realm != challenge.realm);
+                challenges.push(Cached {
+                    origin,
+                    challenge,
+                    nc: 1,
+                });
+            }
+            client.send(req).await
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// Set the `Authorization` header on `req` answering `challenge`.
+fn authorize(
+    req: &mut Request,
+    credentials: &Credentials,
+    challenge: &Challenge,
+    nc: u32,
+) -> Result<(), Error> {
+    let url = req.url();
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let hash = |data: String| challenge.algorithm.hash(data.as_bytes());
+
+    let ha1 = hash(format!(
+        "{}:{}:{}",
+        credentials.username, challenge.realm, credentials.password
+    ));
+    let ha2 = hash(format!("{}:{}", req.method(), uri));
+
+    // A username which can't go in a quoted string is sent in RFC 7616's `username*` form.
+    let username = &credentials.username;
+    let username = if username.bytes().all(|b| b == b' ' || b.is_ascii_graphic()) {
+        format!("username=\"{}\"", quote(username))
+    } else {
+        format!("username*=UTF-8''{}", percent_encode(username))
+    };
+    let mut value = format!(
+        "Digest {}, realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}",
+        username,
+        quote(&challenge.realm),
+        quote(&challenge.nonce),
+        quote(&uri),
+        challenge.algorithm.name(),
+    );
+    // UNWRAPs: writing to a `String` can't fail.
+    if challenge.qop_auth {
+        let cnonce = cnonce()?;
+        let nc = format!("{:08x}", nc);
+        let response = hash(format!(
+            "{}:{}:{}:{}:auth:{}",
+            ha1, challenge.nonce, nc, cnonce, ha2
+        ));
+        write!(
+            value,
+            ", response=\"{}\", qop=auth, nc={}, cnonce=\"{}\"",
+            response, nc, cnonce
+        )
+        .unwrap();
+    } else {
+        let response = hash(format!("{}:{}:{}", ha1, challenge.nonce, ha2));
+        write!(value, ", response=\"{}\"", response).unwrap();
+    }
+    if let Some(opaque) = &challenge.opaque {
+        write!(value, ", opaque=\"{}\"", quote(opaque)).unwrap();
+    }
+
+    let value = HeaderValue::from_bytes(value.into_bytes()).map_err(|_| {
+        Error::from_str(
+            StatusCode::BadGateway,
+            "digest challenge contains non-ASCII parameters",
+        )
+    })?;
+    req.insert_header(AUTHORIZATION, value);
+    Ok(())
+}
+
+/// A random client nonce.
+fn cnonce() -> Result<String, Error> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(|err| {
+        Error::from_str(
+            StatusCode::InternalServerError,
+            format!("failed to generate a client nonce: {}", err),
+        )
+    })?;
+    Ok(hex(&bytes))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        // UNWRAP: writing to a `String` can't fail.
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// Percent-encode the UTF-8 bytes of `value` as an RFC 8187 extended value.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            // UNWRAP: writing to a `String` can't fail.
+            write!(encoded, "%{:02X}", byte).unwrap();
+        }
+    }
+    encoded
+}
+
+/// Escape `value` for use in a quoted string.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    fn hash(self, data: &[u8]) -> String {
+        match self {
+            Algorithm::Md5 => hex(&md5::Md5::digest(data)),
+            Algorithm::Sha256 => hex(&sha2::Sha256::digest(data)),
+        }
+    }
+}
+
+/// A supported `Digest` challenge.
+#[derive(Debug, Clone)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    qop_auth: bool,
+}
+
+impl Challenge {
+    /// The strongest supported `Digest` challenge in the `WWW-Authenticate` headers of `res`.
+    fn from_response(res: &Response) -> Option<Self> {
+        res.header(WWW_AUTHENTICATE)?
+            .iter()
+            .flat_map(|value| split_challenges(value.as_str()))
+            .filter_map(|(scheme, params)| {
+                if scheme.eq_ignore_ascii_case("digest") {
+                    Self::from_params(&params)
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|challenge| challenge.algorithm == Algorithm::Sha256)
+    }
+
+    fn from_params(params: &[(String, String)]) -> Option<Self> {
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+
+        let algorithm = match param("algorithm") {
+            None => Algorithm::Md5,
+            Some(name) if name.eq_ignore_ascii_case("MD5") => Algorithm::Md5,
+            Some(name) if name.eq_ignore_ascii_case("SHA-256") => Algorithm::Sha256,
+            Some(_) => return None,
+        };
+        let qop_auth = match param("qop") {
+            None => false,
+            Some(qop) if qop.split(',').any(|qop| qop.trim() == "auth") => true,
+            Some(_) => return None,
+        };
+
+        Some(Self {
+            realm: param("realm")?.to_string(),
+            nonce: param("nonce")?.to_string(),
+            opaque: param("opaque").map(str::to_string),
+            algorithm,
+            qop_auth,
+        })
+    }
+}
+
+/// Split a `WWW-Authenticate` value into its challenges, each a scheme and its parameters.
+///
+/// Malformed input ends the list early rather than failing, since the response is returned
+/// as-is when no challenge is usable.
+fn split_challenges(value: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut challenges: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    let mut rest = value.trim_start();
+
+    while !rest.is_empty() {
+        let end = rest.find([' ', ',', '=']).unwrap_or(rest.len());
+        let token = &rest[..end];
+        let after = rest[end..].trim_start();
+
+        if let Some(after) = after.strip_prefix('=') {
+            // A parameter of the current challenge.
+            let after = after.trim_start();
+            let (value, after) = match after.strip_prefix('"') {
+                Some(quoted) => match unquote(quoted) {
+                    Some(parsed) => parsed,
+                    None => break,
+                },
+                None => {
+                    let end = after.find(',').unwrap_or(after.len());
+                    (after[..end].trim_end().to_string(), &after[end..])
+                }
+            };
+            match challenges.last_mut() {
+                Some((_, params)) => params.push((token.to_string(), value)),
+                None => break,
+            }
+            rest = after;
+        } else if token.is_empty() {
+            rest = after;
+        } else {
+            challenges.push((token.to_string(), Vec::new()));
+            rest = after;
+        }
+        rest = rest.trim_start_matches([',', ' ']);
+    }
+
+    challenges
+}
+
+/// Read a quoted string whose opening quote has been consumed, returning its value and the
+/// input after the closing quote.
+fn unquote(input: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &input[i + 1..])),
+            '\\' => value.push(chars.next()?.1),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use async_std::prelude::*;
+    use async_std::task;
+    use http_types::url::Url;
+    use http_types::Method;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_challenges() {
+        let challenges = split_challenges(
+            r#"Basic realm="a", Digest realm="b \"q\"", qop="auth,auth-int", nonce=abc"#,
+        );
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].0, "Basic");
+        assert_eq!(challenges[1].0, "Digest");
+        assert_eq!(
+            challenges[1].1,
+            [
+                ("realm".to_string(), "b \"q\"".to_string()),
+                ("qop".to_string(), "auth,auth-int".to_string()),
+                ("nonce".to_string(), "abc".to_string()),
+            ]
+        );
+
+        let challenge = Challenge::from_params(&challenges[1].1).unwrap();
+        assert_eq!(challenge.algorithm, Algorithm::Md5);
+        assert!(challenge.qop_auth);
+    }
+
+    /// Check an `Authorization` header the way a server would, returning its nonce count.
+    fn verify(req: &tide::Request<()>, algorithm: Algorithm) -> Option<String> {
+        let value = req.header("authorization")?[0].as_str().to_string();
+        let (scheme, params) = split_challenges(&value).pop()?;
+        assert_eq!(scheme, "Digest");
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+
+        let ha1 = algorithm.hash(b"user:test realm:secret");
+        let ha2 = algorithm.hash(format!("{}:{}", req.method(), param("uri")).as_bytes());
+        let expected = algorithm.hash(
+            format!(
+                "{}:server-nonce:{}:{}:auth:{}",
+                ha1,
+                param("nc"),
+                param("cnonce"),
+                ha2
+            )
+            .as_bytes(),
+        );
+        assert_eq!(param("opaque"), "xyz");
+        if param("response") == expected {
+            Some(param("nc"))
+        } else {
+            None
+        }
+    }
+
+    /// Challenges requests to `a.example` until they're authorized, and records whether each
+    /// request it receives carries an `Authorization` header, and the last one sent.
+    #[derive(Debug, Clone, Default)]
+    struct Recorder {
+        authorized: Arc<std::sync::Mutex<Vec<(String, bool)>>>,
+        authorization: Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    impl HttpClient for Recorder {
+        fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            // None of the requests sent have a body, so none should gain a `Content-Type`.
+            assert!(req.header(http_types::headers::CONTENT_TYPE).is_none());
+            let host = req.url().host_str().unwrap().to_string();
+            let authorized = req.header(AUTHORIZATION).is_some();
+            if let Some(value) = req.header(AUTHORIZATION) {
+                *self.authorization.lock().unwrap() = Some(value.as_str().to_string());
+            }
+            self.authorized
+                .lock()
+                .unwrap()
+                .push((host.clone(), authorized));
+            Box::pin(async move {
+                if host == "a.example" && !authorized {
+                    let mut res = Response::new(StatusCode::Unauthorized);
+                    res.insert_header(WWW_AUTHENTICATE, r#"Digest realm="r", nonce="n""#);
+                    return Ok(res);
+                }
+                Ok(Response::new(StatusCode::Ok))
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn sends_non_ascii_usernames_encoded() -> http_types::Result<()> {
+        let recorder = Recorder::default();
+        let client = DigestAuthClient::new(recorder.clone(), "jöhn \"j\"", "secret");
+
+        let res = client
+            .send(Request::new(Method::Get, "http://a.example/"))
+            .await?;
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let authorization = recorder.authorization.lock().unwrap().clone().unwrap();
+        assert!(authorization.starts_with("Digest username*=UTF-8''j%C3%B6hn%20%22j%22, "));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn preauthorizes_only_the_challenging_origin() -> http_types::Result<()> {
+        let recorder = Recorder::default();
+        let client = DigestAuthClient::new(recorder.clone(), "user", "secret");
+
+        for url in &[
+            "http://a.example/",
+            "http://b.example/",
+            "http://a.example/",
+        ] {
+            let res = client.send(Request::new(Method::Get, *url)).await?;
+            assert_eq!(res.status(), StatusCode::Ok);
+        }
+
+        let authorized = recorder.authorized.lock().unwrap().clone();
+        let expected = [
+            ("a.example", false),
+            ("a.example", true),
+            ("b.example", false),
+            ("a.example", true),
+        ];
+        let authorized: Vec<_> = authorized
+            .iter()
+            .map(|(host, authorized)| (host.as_str(), *authorized))
+            .collect();
+        assert_eq!(authorized, expected);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn answers_digest_challenge() -> http_types::Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        for (path, algorithm) in &[("/md5", Algorithm::Md5), ("/sha256", Algorithm::Sha256)] {
+            let algorithm = *algorithm;
+            app.at(path)
+                .post(move |mut r: tide::Request<()>| async move {
+                    let nc = verify(&r, algorithm);
+                    let body = r.body_string().await?;
+                    let response = match nc {
+                        Some(nc) => tide::Response::new(http_types::StatusCode::Ok)
+                            .body_string(format!("{} {}", nc, body)),
+                        None => {
+                            let challenge = format!(
+                            "Digest realm=\"test realm\", qop=\"auth\", nonce=\"server-nonce\", \
+                             opaque=\"xyz\", algorithm={}",
+                            algorithm.name()
+                        );
+                            tide::Response::new(http_types::StatusCode::Unauthorized)
+                                .set_header("www-authenticate", challenge)
+                        }
+                    };
+                    Ok(response)
+                });
+        }
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let client = DigestAuthClient::new(H1Client::new(), "user", "secret");
+
+            for path in &["md5", "sha256"] {
+                let url = Url::parse(&format!("http://localhost:{}/{}?a=1", port, path)).unwrap();
+                let mut req = Request::new(Method::Post, url);
+                req.set_body("hello");
+                let mut res = client.send(req).await?;
+                assert_eq!(res.status(), StatusCode::Ok);
+                assert_eq!(res.body_string().await?, "00000001 hello");
+            }
+
+            // The remembered challenge authorizes the next request without a round trip.
+            let url = Url::parse(&format!("http://localhost:{}/sha256", port)).unwrap();
+            let mut res = client.send(Request::new(Method::Post, url)).await?;
+            assert_eq!(res.body_string().await?, "00000002 ");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! Decode the chunked transfer-coding as a body streams.
+
+use super::parse::MAX_HEAD_LENGTH;
+
+use futures::io::{AsyncBufRead, AsyncRead};
+use futures::ready;
+use std::convert::TryFrom;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Maximum length of a chunk-size line in bytes, including any chunk extensions.
+pub(crate) const MAX_SIZE_LINE_LENGTH: usize = 1024;
+
+/// A reader over the data of a chunked body, discarding chunk extensions and trailers.
+///
+/// Chunk data is passed through as it arrives, so a chunk is never held in memory whole,
+/// whatever size the server declares for it. Malformed framing fails reads with
+/// `io::ErrorKind::InvalidData`, and a connection closing partway with `UnexpectedEof`.
+pub(crate) struct ChunkedDecoder<R> {
+    reader: R,
+    state: State,
+    /// The part of the current line read so far.
+    line: Vec<u8>,
+    /// The length of the trailer section read so far.
+    trailer_len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Reading a chunk-size line.
+    Size,
+    /// Reading chunk data, with this many bytes left.
+    Data(u64),
+    /// Reading the line ending after chunk data.
+    DataEnd,
+    /// Reading the trailer section, up to its empty line.
+    Trailer,
+    Done,
+}
+
+impl<R> ChunkedDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: State::Size,
+            line: Vec::new(),
+            trailer_len: 0,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for ChunkedDecoder<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            match this.state {
+                State::Size => {
+                    ready!(poll_line(
+                        &mut this.reader,
+                        &mut this.line,
+                        MAX_SIZE_LINE_LENGTH,
+                        cx
+                    ))?;
+                    let size =
+                        chunk_size(&this.line).ok_or_else(|| invalid("invalid chunk size"))?;
+                    this.line.clear();
+                    this.state = if size == 0 {
+                        State::Trailer
+                    } else {
+                        State::Data(size)
+                    };
+                }
+                State::Data(remaining) => {
+                    if buf.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let available = ready!(Pin::new(&mut this.reader).poll_fill_buf(cx))?;
+                    if available.is_empty() {
+                        return Poll::Ready(Err(closed()));
+                    }
+                    let len = available
+                        .len()
+                        .min(buf.len())
+                        .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                    buf[..len].copy_from_slice(&available[..len]);
+                    Pin::new(&mut this.reader).consume(len);
+                    this.state = match remaining - len as u64 {
+                        0 => State::DataEnd,
+                        remaining => State::Data(remaining),
+                    };
+                    return Poll::Ready(Ok(len));
+                }
+                State::DataEnd => {
+                    ready!(poll_line(&mut this.reader, &mut this.line, 0, cx))?;
+                    this.state = State::Size;
+                }
+                State::Trailer => {
+                    let limit = MAX_HEAD_LENGTH.saturating_sub(this.trailer_len);
+                    ready!(poll_line(&mut this.reader, &mut this.line, limit, cx))?;
+                    this.trailer_len += this.line.len() + 2;
+                    if this.line.is_empty() {
+                        this.state = State::Done;
+                    }
+                    this.line.clear();
+                }
+                State::Done => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+}
+
+/// Read the rest of a line into `line`, without its line ending, failing if it's longer than
+/// `limit` bytes.
+fn poll_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+    limit: usize,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    loop {
+        let available = ready!(Pin::new(&mut *reader).poll_fill_buf(cx))?;
+        if available.is_empty() {
+            return Poll::Ready(Err(closed()));
+        }
+        let (used, done) = match available.iter().position(|b| *b == b'\n') {
+            Some(end) => (end + 1, true),
+            None => (available.len(), false),
+        };
+        line.extend_from_slice(&available[..used]);
+        Pin::new(&mut *reader).consume(used);
+
+        if done {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        // Until the line ends, its last byte may be the carriage return of its line ending.
+        if line.len() > limit + usize::from(!done) {
+            return Poll::Ready(Err(invalid(if limit == 0 {
+                "missing line ending after chunk data"
+            } else {
+                "chunked body line too long"
+            })));
+        }
+        if done {
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+/// Parse the size from a chunk-size line, ignoring any chunk extensions.
+pub(crate) fn chunk_size(line: &[u8]) -> Option<u64> {
+    let size = line.split(|b| *b == b';').next().unwrap_or(&[]);
+    std::str::from_utf8(size)
+        .ok()
+        .map(str::trim)
+        .filter(|size| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|size| u64::from_str_radix(size, 16).ok())
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn closed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed in the middle of a chunked body",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::{AsyncReadExt, Cursor};
+
+    async fn decode(body: &'static [u8]) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        ChunkedDecoder::new(Cursor::new(body))
+            .read_to_end(&mut decoded)
+            .await?;
+        Ok(decoded)
+    }
+
+    #[async_std::test]
+    async fn decodes_chunks() -> io::Result<()> {
+        let body = decode(b"5;ext=1\r\nhello\r\n1\r\n \r\n5\r\nworld\r\n0\r\nx-trailer: 1\r\n\r\n");
+        assert_eq!(body.await?, b"hello world");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rejects_bad_framing() {
+        for body in &[
+            &b"zz\r\nhello\r\n0\r\n\r\n"[..],
+            b"5\r\nhello!\r\n0\r\n\r\n",
+            b"ffffffffffffffffff\r\n",
+        ] {
+            let err = decode(body).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        // A huge chunk is passed through as it arrives, until the connection closes.
+        let err = decode(b"ffffffffffffffff\r\nhello").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
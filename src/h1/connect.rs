@@ -0,0 +1,159 @@
+//! Establish the connections requests are sent over.
+
+use super::socks;
+use crate::{Capabilities, Error, Request};
+
+use async_std::net::TcpStream;
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncWrite};
+use http_types::url::Url;
+use http_types::StatusCode;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// A byte stream requests can be sent over.
+///
+/// This is implemented for every `AsyncRead + AsyncWrite` stream which can be sent between
+/// threads.
+pub trait AsyncReadWrite: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static> AsyncReadWrite for T {}
+
+/// Establishes the stream an `H1Client` sends a request over, in place of its built-in TCP and
+/// TLS connections.
+///
+/// # Examples
+///
+/// ```
+/// # // Unix sockets only exist on Unix, so there's nothing to show elsewhere.
+/// # #[cfg(unix)]
+/// # {
+/// use futures::future::BoxFuture;
+/// use http_client::h1::{AsyncReadWrite, Connector, H1Client};
+/// use http_client::{Capabilities, Error, HttpClient};
+/// use http_types::Url;
+///
+/// /// Connect to every origin over a Unix socket.
+/// #[derive(Debug)]
+/// struct UnixConnector;
+///
+/// impl Connector for UnixConnector {
+///     fn connect(&self, _url: &Url) -> BoxFuture<'static, Result<Box<dyn AsyncReadWrite>, Error>> {
+///         Box::pin(async move {
+///             let stream = async_std::os::unix::net::UnixStream::connect("/run/app.sock").await?;
+///             Ok(Box::new(stream) as Box<dyn AsyncReadWrite>)
+///         })
+///     }
+///
+///     fn capabilities(&self) -> Capabilities {
+///         let mut capabilities = Capabilities::default();
+///         capabilities.unix_sockets = true;
+///         capabilities
+///     }
+/// }
+///
+/// let client = H1Client::new().with_connector(UnixConnector);
+/// assert!(client.capabilities().unix_sockets);
+/// # }
+/// ```
+pub trait Connector: Debug + Send + Sync + 'static {
+    /// Open a stream to the origin of `url`.
+    ///
+    /// For `https` URLs the connector is responsible for TLS.
+    fn connect(&self, url: &Url) -> BoxFuture<'static, Result<Box<dyn AsyncReadWrite>, Error>>;
+
+    /// The features the connections this connector opens provide, such as Unix sockets or TLS
+    /// client certificates, for [`H1Client::capabilities`](crate::HttpClient::capabilities) to
+    /// report.
+    ///
+    /// Defaults to none of them.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// An open connection, and the addresses of its ends when it runs over TCP.
+pub(crate) struct Connection {
+    pub(crate) stream: Box<dyn AsyncReadWrite>,
+    pub(crate) peer_addr: Option<SocketAddr>,
+    pub(crate) local_addr: Option<SocketAddr>,
+}
+
+impl Connection {
+    /// Record the addresses of the connection on `req`.
+    pub(crate) fn set_addrs(&self, req: &mut Request) {
+        req.set_peer_addr(self.peer_addr);
+        req.set_local_addr(self.local_addr);
+    }
+}
+
+/// Connect to the origin of `url` with `connector`, or else over TCP, through `socks_proxy` if
+/// there is one, and with TLS for `https` URLs.
+pub(crate) async fn connect(
+    url: &Url,
+    connector: Option<&Arc<dyn Connector>>,
+    socks_proxy: Option<&Url>,
+) -> Result<Connection, Error> {
+    let host = check_url(url)?;
+    log::trace!("> Scheme: {}", url.scheme());
+
+    if let Some(connector) = connector {
+        return Ok(Connection {
+            stream: connector.connect(url).await?,
+            peer_addr: None,
+            local_addr: None,
+        });
+    }
+
+    let raw_stream = connect_tcp(url, socks_proxy).await?;
+    let peer_addr = raw_stream.peer_addr().ok();
+    let local_addr = raw_stream.local_addr().ok();
+    let stream: Box<dyn AsyncReadWrite> = match url.scheme() {
+        "http" => Box::new(raw_stream),
+        "https" => Box::new(async_native_tls::connect(host, raw_stream).await?),
+        _ => unreachable!(),
+    };
+    Ok(Connection {
+        stream,
+        peer_addr,
+        local_addr,
+    })
+}
+
+/// Validate a request URL, returning its hostname.
+pub(crate) fn check_url(url: &Url) -> Result<String, Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::from_str(StatusCode::BadRequest, "missing hostname"))?
+        .to_string();
+
+    let scheme = url.scheme();
+    if scheme != "http" && scheme != "https" {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            format!("invalid url scheme '{}'", scheme),
+        ));
+    }
+
+    Ok(host)
+}
+
+/// Open a TCP connection to the host of `url`, through `socks_proxy` if there is one.
+async fn connect_tcp(url: &Url, socks_proxy: Option<&Url>) -> Result<TcpStream, Error> {
+    if let Some(proxy) = socks_proxy {
+        return socks::connect(proxy, url).await;
+    }
+
+    let addr = url
+        .socket_addrs(|| match url.scheme() {
+            "http" => Some(80),
+            "https" => Some(443),
+            _ => None,
+        })?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::from_str(StatusCode::BadRequest, "missing valid address"))?;
+
+    Ok(TcpStream::connect(addr).await?)
+}
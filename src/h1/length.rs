@@ -0,0 +1,153 @@
+//! Detect bodies which end before their `Content-Length`.
+
+use crate::{Body, Error, Response};
+
+use futures::io::{AsyncBufRead, AsyncRead, AsyncReadExt, BufReader, Take};
+use http_types::headers::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http_types::{Method, StatusCode};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The error a response body read fails with when the connection closes before the body has
+/// delivered as many bytes as its `Content-Length` promised.
+///
+/// Reads fail with an `io::Error` of kind `UnexpectedEof` wrapping this, rather than returning
+/// a truncated body.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::h1::IncompleteBody;
+///
+/// fn incomplete_body(err: &http_types::Error) -> Option<&IncompleteBody> {
+///     err.downcast_ref::<std::io::Error>()?
+///         .get_ref()?
+///         .downcast_ref::<IncompleteBody>()
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncompleteBody {
+    /// The length the `Content-Length` header declared.
+    pub expected: u64,
+    /// The number of bytes received before the connection closed.
+    pub received: u64,
+}
+
+impl fmt::Display for IncompleteBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "connection closed after {} of {} body bytes",
+            self.received, self.expected
+        )
+    }
+}
+
+impl std::error::Error for IncompleteBody {}
+
+impl IncompleteBody {
+    pub(crate) fn into_io_error(self) -> io::Error {
+        io::Error::new(io::ErrorKind::UnexpectedEof, self)
+    }
+}
+
+/// The body length `res` declares with `Content-Length`, if its body is framed by it.
+pub(crate) fn declared_len(method: Method, res: &Response) -> Result<Option<u64>, Error> {
+    let status: u16 = res.status().into();
+    if method == Method::Head || status == 204 || status == 304 {
+        return Ok(None);
+    }
+    if res.header(TRANSFER_ENCODING).is_some() {
+        return Ok(None);
+    }
+    match res.header(CONTENT_LENGTH) {
+        Some(values) => parse(values.iter().map(|value| value.as_str())).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parse the values of every `Content-Length` header of a response.
+///
+/// Repeated values, in separate headers or a list, are accepted when they're all the same, but
+/// differing values make the body length unknowable, so they're rejected
+/// ([RFC 9112 §6.3](https://www.rfc-editor.org/rfc/rfc9112#section-6.3)).
+pub(crate) fn parse<'a>(values: impl Iterator<Item = &'a str>) -> Result<u64, Error> {
+    let invalid = || Error::from_str(StatusCode::BadGateway, "invalid content-length");
+    let mut len = None;
+    for value in values.flat_map(|value| value.split(',')) {
+        let value = value.trim();
+        if !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let value = value.parse().map_err(|_| invalid())?;
+        if *len.get_or_insert(value) != value {
+            return Err(Error::from_str(
+                StatusCode::BadGateway,
+                "conflicting content-length values",
+            ));
+        }
+    }
+    len.ok_or_else(invalid)
+}
+
+/// A body of the `expected` length read from `reader`, whose reads fail with `IncompleteBody`
+/// if it ends early.
+pub(crate) fn checked<R>(reader: R, expected: u64) -> Body
+where
+    R: AsyncBufRead + Send + Sync + Unpin + 'static,
+{
+    let body = Checked {
+        body: reader.take(expected),
+        expected,
+        received: 0,
+    };
+    Body::from_reader(BufReader::new(body), usize::try_from(expected).ok())
+}
+
+struct Checked<R> {
+    body: Take<R>,
+    expected: u64,
+    received: u64,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Checked<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        self.received += read as u64;
+        if read == 0 && !buf.is_empty() && self.received < self.expected {
+            let err = IncompleteBody {
+                expected: self.expected,
+                received: self.received,
+            };
+            return Poll::Ready(Err(err.into_io_error()));
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_repeated_lengths() {
+        assert_eq!(parse(["5"].iter().copied()).unwrap(), 5);
+        assert_eq!(parse(["5", "5, 5"].iter().copied()).unwrap(), 5);
+    }
+
+    #[test]
+    fn rejects_conflicting_lengths() {
+        let err = parse(["5", "6"].iter().copied()).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        assert_eq!(err.to_string(), "conflicting content-length values");
+        assert!(parse(["5, 6"].iter().copied()).is_err());
+        assert!(parse(["+5"].iter().copied()).is_err());
+    }
+}
@@ -0,0 +1,1297 @@
+//! http-client implementation for async-h1.
+
+use super::alt_svc::AltSvcCache;
+use super::bodyless;
+use super::headers::AcceptLanguage;
+use super::transform::{self, BodyTransform};
+use super::{Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use futures::io::BufReader;
+use http_types::url::Url;
+use http_types::{Mime, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod chunked;
+mod connect;
+mod length;
+mod parse;
+mod pipeline;
+mod raw;
+mod session;
+mod socks;
+mod timeout;
+
+pub use connect::{AsyncReadWrite, Connector};
+pub use length::IncompleteBody;
+pub use parse::{parse_response_head, ParseError, ResponseHead};
+pub use session::Session;
+use timeout::FirstByte;
+pub use timeout::FirstByteTimeout;
+
+/// Async-h1 based HTTP Client.
+///
+/// Request bodies of unknown length are sent with chunked encoding, and each chunk is written to
+/// the connection as soon as the body yields it, so streaming bodies are delivered
+/// incrementally.
+///
+/// Response bodies stream from the connection as they're read. The `gzip` and `deflate`
+/// transfer-codings are undone as well as chunked, as in `Transfer-Encoding: gzip, chunked`,
+/// but a body with them is read into memory first, and fails past 256 MiB.
+#[derive(Debug)]
+pub struct H1Client {
+    alt_svc: Option<AltSvcCache>,
+    transform: Option<Arc<dyn BodyTransform>>,
+    socks_proxy: Option<Url>,
+    strip_bodies: bool,
+    accept_language: Option<AcceptLanguage>,
+    first_byte_timeout: Option<Duration>,
+    default_content_type: Option<Mime>,
+    connector: Option<Arc<dyn Connector>>,
+}
+
+impl Default for H1Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl H1Client {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self {
+            alt_svc: None,
+            transform: None,
+            socks_proxy: None,
+            strip_bodies: false,
+            accept_language: None,
+            first_byte_timeout: None,
+            default_content_type: None,
+            connector: None,
+        }
+    }
+
+    /// Record the `Alt-Svc` header of every response in `cache`.
+    pub fn with_alt_svc_cache(mut self, cache: AltSvcCache) -> Self {
+        self.alt_svc = Some(cache);
+        self
+    }
+
+    /// The cache `Alt-Svc` advertisements are recorded in, if any.
+    pub fn alt_svc_cache(&self) -> Option<&AltSvcCache> {
+        self.alt_svc.as_ref()
+    }
+
+    /// Apply `transform` to the body of every response.
+    pub fn with_body_transform(mut self, transform: impl BodyTransform) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Connect through the SOCKS5 proxy at `proxy`.
+    ///
+    /// The proxy URL must use the `socks5` or `socks5h` scheme, and may carry a username and
+    /// password. With `socks5h` the proxy resolves the hostnames of requests, otherwise they're
+    /// resolved locally.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http_client::h1::H1Client;
+    /// use http_types::Url;
+    ///
+    /// let proxy = Url::parse("socks5h://localhost:9050").unwrap();
+    /// let client = H1Client::new().with_socks_proxy(proxy).unwrap();
+    /// ```
+    pub fn with_socks_proxy(mut self, proxy: Url) -> Result<Self, Error> {
+        socks::check_proxy(&proxy)?;
+        self.socks_proxy = Some(proxy);
+        Ok(self)
+    }
+
+    /// Drop the body, and its `Content-Length`, from `GET`, `HEAD`, `DELETE`, `OPTIONS`, `TRACE`
+    /// and `CONNECT` requests instead of sending it.
+    ///
+    /// Disabled by default, in which case bodies are sent as-is.
+    pub fn strip_body_on_bodyless_methods(mut self, strip: bool) -> Self {
+        self.strip_bodies = strip;
+        self
+    }
+
+    /// Set `Accept-Language` to `lang` on every request which doesn't already have the header.
+    pub fn with_accept_language(mut self, lang: AcceptLanguage) -> Self {
+        self.accept_language = Some(lang);
+        self
+    }
+
+    /// Fail requests that go longer than `timeout` between being sent and the first byte of
+    /// the response arriving, with a [`FirstByteTimeout`] error.
+    ///
+    /// This is independent of how long the rest of the response takes, so a server that starts
+    /// responding promptly but sends its body slowly isn't affected. A [`Session`] applies it to
+    /// each of its requests, and a [`pipeline`](H1Client::pipeline) to the first response, since
+    /// the others are read back-to-back after it.
+    ///
+    /// Only the h1 backend has this option. isahc 0.9 exposes neither a first-byte timeout
+    /// nor curl's low-speed limits, so `IsahcClient` can only bound whole requests.
+    pub fn with_first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
+
+    /// Give responses which arrive without a `Content-Type` header `mime` as their content type.
+    ///
+    /// Without this, such responses report `application/octet-stream`, as http-types gives any
+    /// body without a type, which can't be told apart from a server sending it.
+    pub fn with_default_content_type(mut self, mime: Mime) -> Self {
+        self.default_content_type = Some(mime);
+        self
+    }
+
+    /// Open connections with `connector` instead of the built-in TCP and TLS connections.
+    ///
+    /// A SOCKS proxy set with `with_socks_proxy` is ignored while a connector is set, and
+    /// requests get no peer or local address.
+    pub fn with_connector(mut self, connector: impl Connector) -> Self {
+        self.connector = Some(Arc::new(connector));
+        self
+    }
+}
+
+impl Clone for H1Client {
+    fn clone(&self) -> Self {
+        Self {
+            alt_svc: self.alt_svc.clone(),
+            transform: self.transform.clone(),
+            socks_proxy: self.socks_proxy.clone(),
+            strip_bodies: self.strip_bodies,
+            accept_language: self.accept_language.clone(),
+            first_byte_timeout: self.first_byte_timeout,
+            default_content_type: self.default_content_type.clone(),
+            connector: self.connector.clone(),
+        }
+    }
+}
+
+impl H1Client {
+    /// Send several requests back-to-back over a single connection, without waiting for each
+    /// response before writing the next request (HTTP/1.1 pipelining).
+    ///
+    /// Responses are returned in the same order as `reqs`. Every request must target the same
+    /// origin, and only bodyless `GET`, `HEAD` and `OPTIONS` requests are accepted, since those
+    /// are safe to replay if the connection fails partway.
+    ///
+    /// # Hazards
+    ///
+    /// - Many servers and proxies do not support pipelining. If the server closes the connection
+    ///   early or sends a response without framing, the connection is dropped and an error is
+    ///   returned for the whole batch.
+    /// - Responses are read strictly in order, so one slow response delays every response after
+    ///   it (head-of-line blocking).
+    /// - Response bodies are read into memory in full before this returns, and fail with a
+    ///   `502 Bad Gateway` error past 256 MiB.
+    ///
+    /// A `101 Switching Protocols` response is returned with an empty body, since the connection
+    /// no longer speaks HTTP after it, so it can only answer the last request.
+    pub async fn pipeline(&self, mut reqs: Vec<Request>) -> Result<Vec<Response>, Error> {
+        let first = match reqs.first() {
+            Some(req) => req.url().clone(),
+            None => return Ok(Vec::new()),
+        };
+        for req in &reqs {
+            pipeline::check_request(req)?;
+            let url = req.url();
+            if url.scheme() != first.scheme()
+                || url.host_str() != first.host_str()
+                || url.port_or_known_default() != first.port_or_known_default()
+            {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    "pipelined requests must share an origin",
+                ));
+            }
+        }
+
+        let conn =
+            connect::connect(&first, self.connector.as_ref(), self.socks_proxy.as_ref()).await?;
+        for req in &mut reqs {
+            if let Some(lang) = &self.accept_language {
+                lang.apply(req);
+            }
+            conn.set_addrs(req);
+        }
+
+        let stream = FirstByte::new(conn.stream, self.first_byte_timeout);
+        let mut responses = pipeline::pipeline(stream, reqs, self.default_content_type.as_ref())
+            .await
+            .map_err(timeout::map_error)?;
+
+        for res in &mut responses {
+            if let Some(transform) = &self.transform {
+                transform::apply(transform.as_ref(), res);
+            }
+            if let Some(cache) = &self.alt_svc {
+                cache.record(&first, res);
+            }
+        }
+        Ok(responses)
+    }
+}
+
+impl H1Client {
+    /// Open a [`Session`]: a connection to the origin of `url` which every request sent through
+    /// the session uses, and nothing else does.
+    pub async fn session(&self, url: &Url) -> Result<Session, Error> {
+        let conn =
+            connect::connect(url, self.connector.as_ref(), self.socks_proxy.as_ref()).await?;
+        Ok(Session::new(
+            url.clone(),
+            conn.stream,
+            conn.peer_addr,
+            conn.local_addr,
+            self.clone(),
+        ))
+    }
+}
+
+impl H1Client {
+    /// Send `bytes` verbatim as a complete request to the origin of `url`, and return the raw
+    /// bytes of the response, for protocol testing and replaying captured traffic.
+    ///
+    /// The connection is opened as for `send`, through any connector or SOCKS proxy, but
+    /// nothing else of the client's configuration applies: the request target and headers are
+    /// whatever `bytes` says, and the response is neither decoded nor transformed. The response
+    /// ends where its framing says it does, or when the server closes the connection, and any
+    /// interim `1xx` responses are included ahead of it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async_std::task::block_on(async {
+    /// use http_client::h1::H1Client;
+    /// use http_types::Url;
+    ///
+    /// let url = Url::parse("http://example.com/").unwrap();
+    /// let raw = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    /// let res = H1Client::new().send_raw(&url, raw).await?;
+    /// assert!(res.starts_with(b"HTTP/1.1 "));
+    /// # http_types::Result::Ok(())
+    /// # });
+    /// ```
+    pub async fn send_raw(&self, url: &Url, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let conn =
+            connect::connect(url, self.connector.as_ref(), self.socks_proxy.as_ref()).await?;
+        raw::exchange(conn.stream, bytes).await
+    }
+}
+
+impl HttpClient for H1Client {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let this = self.clone();
+        Box::pin(async move {
+            if this.strip_bodies {
+                bodyless::strip_body(&mut req);
+            }
+            if let Some(lang) = &this.accept_language {
+                lang.apply(&mut req);
+            }
+            let url = req.url().clone();
+
+            let conn =
+                connect::connect(&url, this.connector.as_ref(), this.socks_proxy.as_ref()).await?;
+            conn.set_addrs(&mut req);
+            let stream = FirstByte::new(conn.stream, this.first_byte_timeout);
+            let mut res = pipeline::send(
+                BufReader::new(stream),
+                req,
+                this.default_content_type.as_ref(),
+            )
+            .await
+            .map_err(timeout::map_error)?;
+
+            if let Some(transform) = &this.transform {
+                transform::apply(transform.as_ref(), &mut res);
+            }
+            if let Some(cache) = &this.alt_svc {
+                cache.record(&url, &res);
+            }
+            Ok(res)
+        })
+    }
+
+    /// HTTP/1.1 only, plus whatever a custom [`Connector`] reports providing. The built-in TCP
+    /// and TLS connections provide none of the optional features.
+    fn capabilities(&self) -> Capabilities {
+        match &self.connector {
+            Some(connector) => Capabilities {
+                http2: false,
+                metrics: false,
+                ..connector.capabilities()
+            },
+            None => Capabilities::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use async_std::io;
+    use async_std::prelude::*;
+    use async_std::task;
+    use futures::io::AsyncRead;
+    use http_types::url::Url;
+    use http_types::Result;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    fn build_test_request(url: Url) -> Request {
+        let mut req = Request::new(http_types::Method::Post, url);
+        req.set_body("hello");
+        req.append_header("test", "value");
+        req
+    }
+
+    #[async_std::test]
+    async fn basic_functionality() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").all(|mut r: tide::Request<()>| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body(r.body_bytes().await.unwrap());
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let request =
+                build_test_request(Url::parse(&format!("http://localhost:{}/", port)).unwrap());
+            let mut response: Response = H1Client::new().send(request).await?;
+            assert_eq!(response.body_string().await.unwrap(), "hello");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipeline_returns_responses_in_order() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Read all three request heads before writing any response, so the client must have
+        // sent them back-to-back.
+        let server = task::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut reader = async_std::io::BufReader::new(stream.clone());
+            let mut paths = Vec::new();
+            while paths.len() < 3 {
+                let mut line = String::new();
+                reader.read_line(&mut line).await?;
+                if line.starts_with("GET ") {
+                    paths.push(line.split(' ').nth(1).unwrap().to_string());
+                }
+            }
+            let mut stream = stream;
+            for path in paths {
+                let res = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                    path.len(),
+                    path
+                );
+                stream.write_all(res.as_bytes()).await?;
+            }
+            Result::Ok(())
+        });
+
+        let base = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let reqs = ["a", "b", "c"]
+            .iter()
+            .map(|path| Request::new(http_types::Method::Get, base.join(path).unwrap()))
+            .collect();
+        let responses = H1Client::new().pipeline(reqs).await?;
+        server.await?;
+
+        let mut bodies = Vec::new();
+        for mut res in responses {
+            bodies.push(res.body_string().await?);
+        }
+        assert_eq!(bodies, ["/a", "/b", "/c"]);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipeline_rejects_requests_with_bodies() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let err = H1Client::new()
+            .pipeline(vec![build_test_request(url)])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), http_types::StatusCode::BadRequest);
+    }
+
+    #[async_std::test]
+    async fn duplicate_headers_are_preserved() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async move {
+            Ok(tide::Response::new(http_types::StatusCode::Ok)
+                .append_header("Set-Cookie", "a=1")
+                .append_header("Set-Cookie", "b=2"))
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let request = Request::new(http_types::Method::Get, url);
+            let response: Response = H1Client::new().send(request).await?;
+            let cookies: Vec<_> = response["Set-Cookie"].iter().map(|v| v.as_str()).collect();
+            assert_eq!(cookies, ["a=1", "b=2"]);
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    /// Uppercases every ASCII byte of a body.
+    #[derive(Debug)]
+    struct Uppercase;
+
+    impl BodyTransform for Uppercase {
+        fn wrap(&self, _content_type: Option<&str>, body: Body) -> Body {
+            struct Reader(Body);
+
+            impl AsyncRead for Reader {
+                fn poll_read(
+                    mut self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                    buf: &mut [u8],
+                ) -> Poll<io::Result<usize>> {
+                    let read = futures::ready!(Pin::new(&mut self.0).poll_read(cx, buf))?;
+                    buf[..read].make_ascii_uppercase();
+                    Poll::Ready(Ok(read))
+                }
+            }
+
+            let len = body.len();
+            Body::from_reader(io::BufReader::new(Reader(body)), len)
+        }
+    }
+
+    #[async_std::test]
+    async fn applies_body_transform() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").all(|mut r: tide::Request<()>| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body(r.body_bytes().await.unwrap());
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let request =
+                build_test_request(Url::parse(&format!("http://localhost:{}/", port)).unwrap());
+            let client = H1Client::new().with_body_transform(Uppercase);
+            let mut response: Response = client.send(request).await?;
+            assert_eq!(response.body_string().await.unwrap(), "HELLO");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strips_body_on_bodyless_methods() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|mut r: tide::Request<()>| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body(r.body_bytes().await.unwrap());
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let get = || {
+                let mut req = Request::new(http_types::Method::Get, url.clone());
+                req.set_body("hello");
+                req
+            };
+
+            let client = H1Client::new();
+            let mut response = client.send(get()).await?;
+            assert_eq!(response.body_string().await?, "hello");
+
+            let client = client.strip_body_on_bodyless_methods(true);
+            let mut response = client.send(get()).await?;
+            assert_eq!(response.body_string().await?, "");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn first_byte_timeout() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async move {
+            task::sleep(Duration::from_millis(500)).await;
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body("hello");
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+
+            let client = H1Client::new().with_first_byte_timeout(Duration::from_millis(100));
+            let err = client
+                .send(Request::new(http_types::Method::Get, url.clone()))
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), StatusCode::GatewayTimeout);
+            let timeout = err.downcast_ref::<FirstByteTimeout>().unwrap();
+            assert_eq!(timeout.timeout, Duration::from_millis(100));
+
+            let client = H1Client::new().with_first_byte_timeout(Duration::from_secs(5));
+            let mut response = client
+                .send(Request::new(http_types::Method::Get, url))
+                .await?;
+            assert_eq!(response.body_string().await?, "hello");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn first_byte_timeout_applies_to_sessions_and_pipelines() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Accept connections but never answer on them.
+        let _server: task::JoinHandle<Result<()>> = task::spawn(async move {
+            let mut streams = Vec::new();
+            loop {
+                streams.push(listener.accept().await?.0);
+            }
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let client = H1Client::new().with_first_byte_timeout(Duration::from_millis(100));
+        let is_timeout = |err: Error| {
+            assert_eq!(err.status(), StatusCode::GatewayTimeout);
+            assert!(err.downcast_ref::<FirstByteTimeout>().is_some());
+        };
+
+        let session = client.session(&url).await?;
+        let req = Request::new(http_types::Method::Get, url.clone());
+        is_timeout(session.send(req).await.unwrap_err());
+
+        let req = Request::new(http_types::Method::Get, url);
+        is_timeout(client.pipeline(vec![req]).await.unwrap_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn session_reuses_one_connection() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Answer every request with the number of the connection it arrived on.
+        let server: task::JoinHandle<Result<()>> = task::spawn(async move {
+            let mut connections = 0;
+            loop {
+                let (stream, _) = listener.accept().await?;
+                connections += 1;
+                let id = connections.to_string();
+                task::spawn(async move {
+                    let mut reader = async_std::io::BufReader::new(stream.clone());
+                    let mut stream = stream;
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).await? != 0 {
+                        if line == "\r\n" {
+                            let res = format!(
+                                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                                id.len(),
+                                id
+                            );
+                            stream.write_all(res.as_bytes()).await?;
+                        }
+                        line.clear();
+                    }
+                    Result::Ok(())
+                });
+            }
+        });
+
+        let client = task::spawn(async move {
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let client = H1Client::new();
+            let session = client.session(&url).await?;
+            for path in &["a", "b", "c"] {
+                let req = Request::new(http_types::Method::Get, url.join(path).unwrap());
+                let mut res = session.send(req).await?;
+                assert_eq!(res.body_string().await?, "1");
+            }
+
+            let other = Url::parse("http://example.com/").unwrap();
+            let req = Request::new(http_types::Method::Get, other);
+            assert!(session.send(req).await.is_err());
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn dropped_session_send_closes_the_session() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Answer the first request slowly, and every later one at once, with the request path.
+        let _server: task::JoinHandle<Result<()>> = task::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut reader = async_std::io::BufReader::new(stream.clone());
+            let mut stream = stream;
+            let mut path = String::new();
+            let mut line = String::new();
+            let mut first = true;
+            while reader.read_line(&mut line).await? != 0 {
+                if line.starts_with("GET ") {
+                    path = line.split(' ').nth(1).unwrap().to_string();
+                }
+                if line == "\r\n" {
+                    if first {
+                        task::sleep(Duration::from_millis(300)).await;
+                        first = false;
+                    }
+                    let res = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                        path.len(),
+                        path
+                    );
+                    stream.write_all(res.as_bytes()).await?;
+                }
+                line.clear();
+            }
+            Result::Ok(())
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let session = H1Client::new().session(&url).await?;
+        let req = Request::new(http_types::Method::Get, url.join("first").unwrap());
+        let send = session.send(req);
+        assert!(async_std::future::timeout(Duration::from_millis(50), send)
+            .await
+            .is_err());
+
+        // The response to the dropped request must not be taken as the answer to this one.
+        let req = Request::new(http_types::Method::Get, url.join("second").unwrap());
+        let err = session.send(req).await.unwrap_err();
+        assert_eq!(err.to_string(), "session is closed");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn close_delimited_response_closes_the_session() -> Result<()> {
+        let connector = CannedConnector::new(b"HTTP/1.1 200 OK\r\n\r\nhello");
+        let client = H1Client::new().with_connector(connector);
+        let url = Url::parse("http://example.invalid/").unwrap();
+
+        let session = client.session(&url).await?;
+        let mut res = session
+            .send(Request::new(http_types::Method::Get, url.clone()))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello");
+
+        let err = session
+            .send(Request::new(http_types::Method::Get, url))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "session is closed");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn streams_request_body_chunks_as_they_are_produced() -> Result<()> {
+        use futures::channel::mpsc;
+        use futures::stream::TryStreamExt;
+
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let (received_tx, mut received) = mpsc::unbounded();
+
+        // Report each chunk of the request body as soon as it arrives.
+        let server = task::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut reader = async_std::io::BufReader::new(stream.clone());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).await?;
+            }
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await?;
+                let size = usize::from_str_radix(line.trim(), 16)?;
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).await?;
+                if size == 0 {
+                    break;
+                }
+                chunk.truncate(size);
+                received_tx.unbounded_send(String::from_utf8(chunk)?)?;
+            }
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await?;
+            Result::Ok(())
+        });
+
+        let (chunks, body) = mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let mut req = Request::new(http_types::Method::Post, url);
+        req.set_body(Body::from_reader(body.into_async_read(), None));
+        let client = task::spawn(async move { H1Client::new().send(req).await });
+
+        // Each chunk must reach the server before the next one is produced.
+        for chunk in &["first", "second"] {
+            chunks.unbounded_send(Ok(chunk.as_bytes().to_vec()))?;
+            assert_eq!(received.next().await.as_deref(), Some(*chunk));
+        }
+        drop(chunks);
+
+        let mut res = client.await?;
+        assert_eq!(res.body_string().await?, "ok");
+        server.await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn short_body_is_an_incomplete_body_error() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Promise ten bytes, send five, then close the connection.
+        let server = task::spawn(async move {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await?;
+                let mut reader = async_std::io::BufReader::new(stream.clone());
+                let mut line = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).await?;
+                }
+                let mut stream = stream;
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\nhello")
+                    .await?;
+            }
+            Result::Ok(())
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let incomplete = |err: &Error| {
+            *err.downcast_ref::<std::io::Error>()
+                .and_then(|err| err.get_ref())
+                .and_then(|err| err.downcast_ref::<IncompleteBody>())
+                .unwrap()
+        };
+        let expected = IncompleteBody {
+            expected: 10,
+            received: 5,
+        };
+
+        let client = H1Client::new();
+        let mut res = client
+            .send(Request::new(http_types::Method::Get, url.clone()))
+            .await?;
+        let err = res.body_bytes().await.unwrap_err();
+        assert_eq!(incomplete(&err), expected);
+
+        let err = client
+            .pipeline(vec![Request::new(http_types::Method::Get, url)])
+            .await
+            .unwrap_err();
+        assert_eq!(incomplete(&err), expected);
+        server.await?;
+
+        Ok(())
+    }
+
+    /// Serves a canned response to every connection, recording what the client writes.
+    #[derive(Debug, Clone)]
+    struct CannedConnector {
+        response: &'static [u8],
+        written: Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl CannedConnector {
+        fn new(response: &'static [u8]) -> Self {
+            Self {
+                response,
+                written: Arc::default(),
+            }
+        }
+    }
+
+    struct CannedStream {
+        response: futures::io::Cursor<&'static [u8]>,
+        written: Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl AsyncRead for CannedStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.response).poll_read(cx, buf)
+        }
+    }
+
+    impl futures::io::AsyncWrite for CannedStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Connector for CannedConnector {
+        fn connect(&self, _url: &Url) -> BoxFuture<'static, Result<Box<dyn AsyncReadWrite>>> {
+            let stream = CannedStream {
+                response: futures::io::Cursor::new(self.response),
+                written: self.written.clone(),
+            };
+            Box::pin(async move { Ok(Box::new(stream) as Box<dyn AsyncReadWrite>) })
+        }
+    }
+
+    #[async_std::test]
+    async fn sends_over_custom_connector() -> Result<()> {
+        let connector = CannedConnector::new(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello");
+        let client = H1Client::new().with_connector(connector.clone());
+
+        // The host doesn't resolve, so this only works without a real socket.
+        let url = Url::parse("http://example.invalid/path").unwrap();
+        let mut res = client
+            .send(Request::new(http_types::Method::Get, url))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello");
+
+        let written = String::from_utf8(connector.written.lock().unwrap().clone())?;
+        assert!(written.starts_with("GET /path HTTP/1.1\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipeline_rejects_hostile_framing() {
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let responses: [&'static [u8]; 2] = [
+            // A chunk far larger than could ever be allocated.
+            b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\nffffffffffffffff\r\nhello",
+            // A header line which never ends.
+            &[b'a'; 64 * 1024],
+        ];
+        for response in &responses {
+            let client = H1Client::new().with_connector(CannedConnector::new(response));
+            let err = client
+                .pipeline(vec![Request::new(http_types::Method::Get, url.clone())])
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), StatusCode::BadGateway);
+        }
+    }
+
+    #[async_std::test]
+    async fn decodes_chunked_bodies_strictly() -> Result<()> {
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let connector = CannedConnector::new(
+            b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5;ext=foo\r\nhello\r\n0\r\n\r\n",
+        );
+        let client = H1Client::new().with_connector(connector);
+        let mut res = client
+            .send(Request::new(http_types::Method::Get, url.clone()))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello");
+
+        for (response, message) in &[
+            (
+                &b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5x\r\nhello\r\n0\r\n\r\n"[..],
+                "invalid chunk size",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello!\r\n0\r\n\r\n",
+                "missing line ending after chunk data",
+            ),
+        ] {
+            let client = H1Client::new().with_connector(CannedConnector::new(response));
+            let mut res = client
+                .send(Request::new(http_types::Method::Get, url.clone()))
+                .await?;
+            let err = res.body_string().await.unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+
+            let client = H1Client::new().with_connector(CannedConnector::new(response));
+            let err = client
+                .pipeline(vec![Request::new(http_types::Method::Get, url.clone())])
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), StatusCode::BadGateway);
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipeline_returns_switching_protocols() -> Result<()> {
+        let connector = CannedConnector::new(
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 101 Switching Protocols\r\nupgrade: x\r\n\r\n\x00\x01",
+        );
+        let client = H1Client::new().with_connector(connector);
+        let url = Url::parse("http://example.invalid/").unwrap();
+
+        let mut responses = client
+            .pipeline(vec![Request::new(http_types::Method::Get, url.clone())])
+            .await?;
+        assert_eq!(responses[0].status(), StatusCode::SwitchingProtocols);
+        assert_eq!(responses[0].body_bytes().await?, b"");
+
+        // The connection can't answer a second request after switching protocols.
+        let reqs = vec![
+            Request::new(http_types::Method::Get, url.clone()),
+            Request::new(http_types::Method::Get, url),
+        ];
+        assert!(client.pipeline(reqs).await.is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn records_alt_svc() -> Result<()> {
+        let connector = CannedConnector::new(
+            b"HTTP/1.1 200 OK\r\nalt-svc: h3=\":443\"\r\ncontent-length: 0\r\n\r\n",
+        );
+        let client = H1Client::new()
+            .with_connector(connector)
+            .with_alt_svc_cache(AltSvcCache::new());
+
+        // Only advertisements received over https are trusted.
+        let url = Url::parse("https://example.invalid/").unwrap();
+        client
+            .send(Request::new(http_types::Method::Get, url.clone()))
+            .await?;
+
+        let alts = client.alt_svc_cache().unwrap().get(&url);
+        assert_eq!(alts.len(), 1);
+        assert_eq!(alts[0].protocol, "h3");
+        assert_eq!(alts[0].host, None);
+        assert_eq!(alts[0].port, 443);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn conflicting_content_lengths_are_rejected() -> Result<()> {
+        let connector = CannedConnector::new(
+            b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\ncontent-length: 6\r\n\r\nhello!",
+        );
+        let client = H1Client::new().with_connector(connector);
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let get = || Request::new(http_types::Method::Get, url.clone());
+
+        let err = client.send(get()).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        let err = client.pipeline(vec![get()]).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        let err = client
+            .send_raw(&url, b"GET / HTTP/1.1\r\nHost: example.invalid\r\n\r\n")
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn applies_default_content_type() -> Result<()> {
+        let untyped: &'static [u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}";
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let get = || Request::new(http_types::Method::Get, url.clone());
+        let client = |response| {
+            H1Client::new()
+                .with_connector(CannedConnector::new(response))
+                .with_default_content_type(http_types::mime::JSON)
+        };
+
+        let mut res = client(untyped).send(get()).await?;
+        assert_eq!(res.content_type(), Some(http_types::mime::JSON));
+        assert_eq!(res.body_string().await?, "{}");
+        let res = client(untyped).pipeline(vec![get()]).await?;
+        assert_eq!(res[0].content_type(), Some(http_types::mime::JSON));
+        let session = client(untyped).session(&url).await?;
+        let res = session.send(get()).await?;
+        assert_eq!(res.content_type(), Some(http_types::mime::JSON));
+
+        let typed = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 2\r\n\r\nhi";
+        let res = client(typed).send(get()).await?;
+        assert_eq!(res["content-type"], "text/plain");
+
+        let client = H1Client::new().with_connector(CannedConnector::new(untyped));
+        let res = client.send(get()).await?;
+        assert_eq!(res.content_type(), Some(http_types::mime::BYTE_STREAM));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn non_ascii_header_values_are_rejected() -> Result<()> {
+        let connector = CannedConnector::new(
+            "HTTP/1.1 200 OK\r\nx-name: café\r\ncontent-length: 0\r\n\r\n".as_bytes(),
+        );
+        let client = H1Client::new().with_connector(connector);
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let get = || Request::new(http_types::Method::Get, url.clone());
+
+        let err = client.send(get()).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        let err = client.pipeline(vec![get()]).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        let session = client.session(&url).await?;
+        let err = session.send(get()).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadGateway);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn decodes_gzip_then_chunked_transfer_coding() -> Result<()> {
+        use flate2::write::GzEncoder;
+        use std::io::Write as _;
+
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"hello gzip")?;
+        let gzip = gzip.finish()?;
+
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        // Answer each request with the gzipped body split into two chunks.
+        let server = task::spawn(async move {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await?;
+                let mut reader = async_std::io::BufReader::new(stream.clone());
+                let mut line = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).await?;
+                }
+                let (first, second) = gzip.split_at(gzip.len() / 2);
+                let mut res =
+                    b"HTTP/1.1 200 OK\r\ntransfer-encoding: gzip, chunked\r\n\r\n".to_vec();
+                for chunk in &[first, second] {
+                    res.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    res.extend_from_slice(chunk);
+                    res.extend_from_slice(b"\r\n");
+                }
+                res.extend_from_slice(b"0\r\n\r\n");
+                let mut stream = stream;
+                stream.write_all(&res).await?;
+            }
+            Result::Ok(())
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let client = H1Client::new();
+        let session = client.session(&url).await?;
+        let mut res = session
+            .send(Request::new(http_types::Method::Get, url.clone()))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello gzip");
+
+        let mut res = client
+            .send(Request::new(http_types::Method::Get, url))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello gzip");
+
+        server.await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn transfer_codings_are_case_insensitive() -> Result<()> {
+        let connector = CannedConnector::new(
+            b"HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        );
+        let client = H1Client::new().with_connector(connector);
+        let url = Url::parse("http://example.invalid/").unwrap();
+        let mut res = client
+            .send(Request::new(http_types::Method::Get, url))
+            .await?;
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sends_raw_request_bytes() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async { Ok("hello") });
+        app.at("/chunked").get(|_| async {
+            let mut res = tide::Response::new(StatusCode::Ok);
+            res.set_body(Body::from_reader(io::Cursor::new("hello chunks"), None));
+            Ok(res)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let client = H1Client::new();
+
+            // The server keeps the connection open, so each response must end at its framing.
+            let res = client
+                .send_raw(&url, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await?;
+            assert!(res.starts_with(b"HTTP/1.1 200"));
+            assert!(res.ends_with(b"\r\n\r\nhello"));
+
+            let res = client
+                .send_raw(&url, b"GET /chunked HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await?;
+            let res = String::from_utf8(res)?;
+            assert!(res.starts_with("HTTP/1.1 200"));
+            assert!(res
+                .to_ascii_lowercase()
+                .contains("transfer-encoding: chunked\r\n"));
+            assert!(res.ends_with("\r\n0\r\n\r\n"));
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn send_raw_bounds_chunked_bodies() {
+        let mut long_line = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n".to_vec();
+        long_line.extend_from_slice(&[b'0'; 2048]);
+        long_line.extend_from_slice(b"1\r\nx\r\n0\r\n\r\n");
+        let long_line: &'static [u8] = Box::leak(long_line.into_boxed_slice());
+
+        for (response, message) in &[
+            (long_line, "chunked body line too long"),
+            // A huge declared chunk is copied as it arrives, not allocated up front.
+            (
+                &b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\nffffffffffff\r\nhello"[..],
+                "connection closed in the middle of a chunked body",
+            ),
+        ] {
+            let client = H1Client::new().with_connector(CannedConnector::new(response));
+            let url = Url::parse("http://example.com/").unwrap();
+            let err = client
+                .send_raw(&url, b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), StatusCode::BadGateway);
+            assert_eq!(err.to_string(), *message);
+        }
+    }
+
+    /// Claims every feature, to check which of them `H1Client` passes on.
+    #[derive(Debug)]
+    struct CapableConnector;
+
+    impl Connector for CapableConnector {
+        fn connect(&self, _url: &Url) -> BoxFuture<'static, Result<Box<dyn AsyncReadWrite>>> {
+            unreachable!()
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                http2: true,
+                unix_sockets: true,
+                local_address_binding: true,
+                metrics: true,
+                tls_client_auth: true,
+            }
+        }
+    }
+
+    #[test]
+    fn reports_capabilities() {
+        assert_eq!(H1Client::new().capabilities(), Capabilities::default());
+        assert_eq!(
+            H1Client::new()
+                .with_connector(CapableConnector)
+                .capabilities(),
+            Capabilities {
+                http2: false,
+                unix_sockets: true,
+                local_address_binding: true,
+                metrics: false,
+                tls_client_auth: true,
+            }
+        );
+    }
+}
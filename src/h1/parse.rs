@@ -0,0 +1,310 @@
+//! A strict HTTP/1.1 response head parser ([RFC 9112](https://www.rfc-editor.org/rfc/rfc9112)).
+
+use http_types::Version;
+use std::fmt;
+
+/// Maximum length of a status line in bytes, excluding its line ending.
+const MAX_STATUS_LINE_LENGTH: usize = 1024;
+
+/// Maximum length of a response head in bytes.
+pub(crate) const MAX_HEAD_LENGTH: usize = 8 * 1024;
+
+/// Maximum number of headers accepted in a single response head.
+const MAX_HEADERS: usize = 128;
+
+/// The status line and headers of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+    /// The HTTP version, either HTTP/1.0 or HTTP/1.1.
+    pub version: Version,
+    /// The three-digit status code.
+    pub status: u16,
+    /// The reason phrase, which may be empty.
+    pub reason: String,
+    /// The headers in the order they were received, with surrounding whitespace removed from
+    /// their values.
+    pub headers: Vec<(String, String)>,
+}
+
+/// An error parsing a response head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The input ends before the end of the head.
+    Incomplete,
+    /// The status line is longer than 1024 bytes.
+    StatusLineTooLong,
+    /// The head is longer than 8 KiB.
+    HeadTooLong,
+    /// The head has more than 128 headers.
+    TooManyHeaders,
+    /// A carriage return isn't followed by a line feed.
+    BareCr,
+    /// The version isn't `HTTP/1.0` or `HTTP/1.1`.
+    InvalidVersion,
+    /// The status code isn't three digits.
+    InvalidStatus,
+    /// The reason phrase contains a control character.
+    InvalidReason,
+    /// A header name is empty, contains a byte that isn't allowed in a token, or is followed by
+    /// whitespace before the colon.
+    InvalidHeaderName,
+    /// A header value contains a control character, or isn't valid UTF-8.
+    InvalidHeaderValue,
+    /// A header is continued on the next line (obsolete line folding).
+    ObsoleteLineFolding,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::Incomplete => "incomplete response head",
+            ParseError::StatusLineTooLong => "status line too long",
+            ParseError::HeadTooLong => "response head too long",
+            ParseError::TooManyHeaders => "too many headers",
+            ParseError::BareCr => "carriage return without a line feed",
+            ParseError::InvalidVersion => "invalid HTTP version",
+            ParseError::InvalidStatus => "invalid status code",
+            ParseError::InvalidReason => "invalid reason phrase",
+            ParseError::InvalidHeaderName => "invalid header name",
+            ParseError::InvalidHeaderValue => "invalid header value",
+            ParseError::ObsoleteLineFolding => "obsolete line folding",
+        })
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a response head from the start of `buf`, returning it with the number of bytes it
+/// took up, including the empty line which terminates it.
+///
+/// Lines may end in CRLF or a bare LF, but a CR anywhere other than before an LF is rejected,
+/// as is obsolete line folding. Returns `ParseError::Incomplete` if `buf` ends before the head
+/// does, in which case more input may complete it.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::h1::parse_response_head;
+///
+/// let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+/// let (head, len) = parse_response_head(buf).unwrap();
+/// assert_eq!(head.status, 200);
+/// assert_eq!(head.headers, [("Content-Length".to_string(), "5".to_string())]);
+/// assert_eq!(&buf[len..], b"hello");
+/// ```
+pub fn parse_response_head(buf: &[u8]) -> Result<(ResponseHead, usize), ParseError> {
+    let mut pos = 0;
+    let line = next_line(
+        buf,
+        &mut pos,
+        MAX_STATUS_LINE_LENGTH,
+        ParseError::StatusLineTooLong,
+    )?;
+    let (version, status, reason) = parse_status_line(line)?;
+
+    let mut headers = Vec::new();
+    loop {
+        let remaining = MAX_HEAD_LENGTH.saturating_sub(pos);
+        let line = next_line(buf, &mut pos, remaining, ParseError::HeadTooLong)?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(ParseError::TooManyHeaders);
+        }
+        headers.push(parse_header(line)?);
+    }
+
+    let head = ResponseHead {
+        version,
+        status,
+        reason,
+        headers,
+    };
+    Ok((head, pos))
+}
+
+/// Read the line starting at `pos`, without its line ending, and move `pos` past it.
+///
+/// Fails with `too_long` if the line is longer than `limit`.
+fn next_line<'a>(
+    buf: &'a [u8],
+    pos: &mut usize,
+    limit: usize,
+    too_long: ParseError,
+) -> Result<&'a [u8], ParseError> {
+    let rest = &buf[*pos..];
+    let lf = match rest.iter().position(|b| *b == b'\n') {
+        Some(lf) => lf,
+        None if rest.len() > limit => return Err(too_long),
+        None => return Err(ParseError::Incomplete),
+    };
+
+    let line = match rest[..lf].split_last() {
+        Some((b'\r', line)) => line,
+        _ => &rest[..lf],
+    };
+    if line.len() > limit {
+        return Err(too_long);
+    }
+    if line.contains(&b'\r') {
+        return Err(ParseError::BareCr);
+    }
+    *pos += lf + 1;
+    Ok(line)
+}
+
+fn parse_status_line(line: &[u8]) -> Result<(Version, u16, String), ParseError> {
+    let version = match line.get(..8) {
+        Some(b"HTTP/1.0") => Version::Http1_0,
+        Some(b"HTTP/1.1") => Version::Http1_1,
+        _ => return Err(ParseError::InvalidVersion),
+    };
+    if line.get(8) != Some(&b' ') {
+        return Err(ParseError::InvalidVersion);
+    }
+
+    let status = match line.get(9..12) {
+        Some(code) if code.iter().all(u8::is_ascii_digit) && code[0] != b'0' => code
+            .iter()
+            .fold(0, |status, digit| status * 10 + u16::from(digit - b'0')),
+        _ => return Err(ParseError::InvalidStatus),
+    };
+
+    // Some servers omit the space before an empty reason phrase.
+    let reason = match line.get(12..) {
+        Some([]) | None => &[][..],
+        Some([b' ', reason @ ..]) => reason,
+        Some(_) => return Err(ParseError::InvalidStatus),
+    };
+    if !reason.iter().all(|b| is_field_byte(*b)) {
+        return Err(ParseError::InvalidReason);
+    }
+
+    Ok((
+        version,
+        status,
+        String::from_utf8_lossy(reason).into_owned(),
+    ))
+}
+
+fn parse_header(line: &[u8]) -> Result<(String, String), ParseError> {
+    if line[0] == b' ' || line[0] == b'\t' {
+        return Err(ParseError::ObsoleteLineFolding);
+    }
+
+    let colon = line
+        .iter()
+        .position(|b| *b == b':')
+        .ok_or(ParseError::InvalidHeaderName)?;
+    let name = &line[..colon];
+    if name.is_empty() || !name.iter().all(|b| is_token_byte(*b)) {
+        return Err(ParseError::InvalidHeaderName);
+    }
+
+    let value = trim_whitespace(&line[colon + 1..]);
+    if !value.iter().all(|b| is_field_byte(*b)) {
+        return Err(ParseError::InvalidHeaderValue);
+    }
+    let value = std::str::from_utf8(value).map_err(|_| ParseError::InvalidHeaderValue)?;
+
+    // UNWRAP: token bytes are ASCII.
+    let name = std::str::from_utf8(name).unwrap();
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Whether `b` may appear in a token, such as a header name.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Whether `b` may appear in a header value or reason phrase: visible characters, spaces, tabs
+/// and obsolete non-ASCII text.
+fn is_field_byte(b: u8) -> bool {
+    b == b'\t' || b == b' ' || b.is_ascii_graphic() || b >= 0x80
+}
+
+fn trim_whitespace(mut value: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = value {
+        value = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = value {
+        value = rest;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_valid_head() {
+        let buf = b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain \r\nX-Empty:\r\n\r\nbody";
+        let (head, len) = parse_response_head(buf).unwrap();
+        assert_eq!(
+            head,
+            ResponseHead {
+                version: Version::Http1_1,
+                status: 404,
+                reason: "Not Found".to_string(),
+                headers: vec![
+                    ("Content-Type".to_string(), "text/plain".to_string()),
+                    ("X-Empty".to_string(), String::new()),
+                ],
+            }
+        );
+        assert_eq!(&buf[len..], b"body");
+
+        assert_eq!(parse_response_head(&buf[..20]), Err(ParseError::Incomplete));
+    }
+
+    #[test]
+    fn rejects_oversized_status_line() {
+        let mut buf = b"HTTP/1.1 200 ".to_vec();
+        buf.resize(MAX_STATUS_LINE_LENGTH + 1, b'a');
+        assert_eq!(
+            parse_response_head(&buf),
+            Err(ParseError::StatusLineTooLong)
+        );
+        buf.extend_from_slice(b"\r\n\r\n");
+        assert_eq!(
+            parse_response_head(&buf),
+            Err(ParseError::StatusLineTooLong)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_header_names() {
+        for buf in &[
+            &b"HTTP/1.1 200 OK\r\nBad Name: 1\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\nBad\x01: 1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nName : 1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\n: 1\r\n\r\n",
+        ] {
+            assert_eq!(parse_response_head(buf), Err(ParseError::InvalidHeaderName));
+        }
+        assert_eq!(
+            parse_response_head(b"HTTP/1.1 200 OK\r\nName: a\x00b\r\n\r\n"),
+            Err(ParseError::InvalidHeaderValue)
+        );
+    }
+
+    #[test]
+    fn accepts_bare_lf_but_rejects_bare_cr() {
+        let (head, len) = parse_response_head(b"HTTP/1.0 200 OK\nA: 1\n\n").unwrap();
+        assert_eq!(head.version, Version::Http1_0);
+        assert_eq!(head.headers, [("A".to_string(), "1".to_string())]);
+        assert_eq!(len, 22);
+
+        assert_eq!(
+            parse_response_head(b"HTTP/1.1 200 OK\rA: 1\r\n\r\n"),
+            Err(ParseError::BareCr)
+        );
+        assert_eq!(
+            parse_response_head(b"HTTP/1.1 200 OK\r\nA: 1\r\n continued\r\n\r\n"),
+            Err(ParseError::ObsoleteLineFolding)
+        );
+    }
+}
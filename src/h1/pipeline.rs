@@ -0,0 +1,388 @@
+//! Send requests over a connection and read their responses: one at a time, with the body
+//! streaming or buffered, or pipelined.
+
+use super::chunked::ChunkedDecoder;
+use super::length::{self, IncompleteBody};
+use super::parse::{parse_response_head, MAX_HEAD_LENGTH};
+use crate::{Body, Error, Request, Response};
+
+use async_h1::client::Encoder;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite};
+use futures::io::{AsyncWriteExt, BufReader};
+use http_types::headers::{
+    HeaderValue, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING,
+};
+use http_types::{Method, Mime, StatusCode, Version};
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+/// Maximum length of a response body read into memory, after undoing its transfer-codings.
+pub(crate) const MAX_BODY_LENGTH: usize = 256 * 1024 * 1024;
+
+/// Check that a request may be pipelined: it must be safe to replay and carry no body.
+pub(crate) fn check_request(req: &Request) -> Result<(), Error> {
+    match req.method() {
+        Method::Get | Method::Head | Method::Options => {}
+        method => {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                format!("cannot pipeline a {} request", method),
+            ))
+        }
+    }
+
+    if req.len() != Some(0) {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            "cannot pipeline a request with a body",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Write every request to `stream`, then read the responses back in order.
+///
+/// Responses without a `Content-Type` are given `default_type`, here and in `send` and
+/// `exchange`, if there is one.
+pub(crate) async fn pipeline<S>(
+    stream: S,
+    reqs: Vec<Request>,
+    default_type: Option<&Mime>,
+) -> Result<Vec<Response>, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let methods: Vec<Method> = reqs.iter().map(|req| req.method()).collect();
+    let mut stream = BufReader::new(stream);
+
+    for req in reqs {
+        let mut encoder = Encoder::new(req);
+        futures::io::copy(&mut encoder, stream.get_mut()).await?;
+    }
+    stream.get_mut().flush().await?;
+
+    let mut responses = Vec::with_capacity(methods.len());
+    for (i, method) in methods.iter().enumerate() {
+        let last = i + 1 == methods.len();
+        let res = read_response(&mut stream, *method, last, default_type).await?;
+        if !last && closes_connection(&res) {
+            return Err(bad_gateway(
+                "server closed the connection before answering every pipelined request",
+            ));
+        }
+        responses.push(res);
+    }
+
+    Ok(responses)
+}
+
+/// Write a single request to `stream`, and read back its response, with a body which streams
+/// from the rest of the connection.
+///
+/// A body with transfer-codings other than chunked is read into memory to undo them.
+pub(crate) async fn send<S>(
+    mut stream: BufReader<S>,
+    req: Request,
+    default_type: Option<&Mime>,
+) -> Result<Response, Error>
+where
+    S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let method = req.method();
+    write_request(&mut stream, req).await?;
+    let mut res = read_response_head(&mut stream, default_type).await?;
+    let status: u16 = res.status().into();
+    if method == Method::Head || matches!(status, 101 | 204 | 304) {
+        return Ok(res);
+    }
+
+    let codings = transfer_codings(&res);
+    if codings == ["chunked"] {
+        let body = BufReader::new(ChunkedDecoder::new(stream));
+        res.set_body(Body::from_reader(body, None));
+    } else if !codings.is_empty() {
+        let body = read_body(&mut stream, method, &res, true).await?;
+        res.set_body(body);
+    } else if let Some(expected) = length::declared_len(method, &res)? {
+        res.set_body(length::checked(stream, expected));
+    } else {
+        res.set_body(Body::from_reader(stream, None));
+    }
+    Ok(res)
+}
+
+/// Write a single request to `stream` and read its response back.
+///
+/// A body delimited by the connection closing is accepted, leaving the connection at its end.
+pub(crate) async fn exchange<S>(
+    stream: &mut BufReader<S>,
+    req: Request,
+    default_type: Option<&Mime>,
+) -> Result<Response, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let method = req.method();
+    write_request(stream, req).await?;
+    read_response(stream, method, true, default_type).await
+}
+
+async fn write_request<S>(stream: &mut BufReader<S>, req: Request) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut encoder = Encoder::new(req);
+    futures::io::copy(&mut encoder, stream.get_mut()).await?;
+    stream.get_mut().flush().await?;
+    Ok(())
+}
+
+/// Read one complete response, buffering its body.
+async fn read_response<R>(
+    reader: &mut R,
+    method: Method,
+    last: bool,
+    default_type: Option<&Mime>,
+) -> Result<Response, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut res = read_response_head(reader, default_type).await?;
+    let body = read_body(reader, method, &res, last).await?;
+    res.set_body(body);
+    Ok(res)
+}
+
+/// Read the head of a final response, skipping any interim responses before it.
+async fn read_response_head<R>(
+    reader: &mut R,
+    default_type: Option<&Mime>,
+) -> Result<Response, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let head = read_head(reader).await?;
+        let (head, _) =
+            parse_response_head(&head).map_err(|err| Error::new(StatusCode::BadGateway, err))?;
+        // Interim responses carry no body; wait for the final one. `101 Switching Protocols`
+        // is final as far as HTTP is concerned, since the connection speaks another protocol
+        // after it.
+        if (100..200).contains(&head.status) && head.status != 101 {
+            continue;
+        }
+
+        let mut res = Response::new(StatusCode::try_from(head.status)?);
+        res.set_version(Some(head.version));
+        for (name, value) in head.headers {
+            // The parser allows obsolete non-ASCII text in values, which http-types can't hold.
+            let value = HeaderValue::from_bytes(value.into_bytes())
+                .map_err(|_| bad_gateway("non-ASCII response header value"))?;
+            res.append_header(name.as_str(), value);
+        }
+        // Before the body is set, since that fills in a missing `Content-Type` with its own.
+        if let Some(mime) = default_type {
+            if res.header(CONTENT_TYPE).is_none() {
+                res.set_content_type(mime.clone());
+            }
+        }
+        return Ok(res);
+    }
+}
+
+/// Read a response head up to and including the empty line which terminates it.
+pub(crate) async fn read_head<R>(reader: &mut R) -> Result<Vec<u8>, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut buf = Vec::new();
+    // Bound the read itself, so a line without an end can't grow the buffer without limit.
+    let mut reader = (&mut *reader).take(MAX_HEAD_LENGTH as u64 + 1);
+    loop {
+        let read = reader.read_until(b'\n', &mut buf).await?;
+        if buf.len() > MAX_HEAD_LENGTH {
+            return Err(bad_gateway("response head too long"));
+        }
+        if read == 0 {
+            return Err(bad_gateway("connection closed before the response head"));
+        }
+        if buf.ends_with(b"\r\n\r\n") || buf.ends_with(b"\n\n") {
+            return Ok(buf);
+        }
+    }
+}
+
+/// Read a response body according to its framing.
+async fn read_body<R>(
+    reader: &mut R,
+    method: Method,
+    res: &Response,
+    last: bool,
+) -> Result<Body, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let status: u16 = res.status().into();
+    if method == Method::Head || matches!(status, 101 | 204 | 304) {
+        return Ok(Body::empty());
+    }
+
+    let mut codings = transfer_codings(res);
+    if !codings.is_empty() {
+        // Transfer-codings are listed in the order they were applied, so undo them in reverse.
+        // A body whose final coding isn't chunked runs until the connection closes.
+        let mut body = if codings.last().map(String::as_str) == Some("chunked") {
+            codings.pop();
+            read_chunked(reader).await?
+        } else {
+            read_to_close(reader, last).await?
+        };
+        for coding in codings.iter().rev() {
+            body = decode(coding, &body)?;
+        }
+        return Ok(Body::from(body));
+    }
+
+    if let Some(expected) = length::declared_len(method, res)? {
+        if expected > MAX_BODY_LENGTH as u64 {
+            return Err(bad_gateway("response body too long"));
+        }
+        let mut body = Vec::new();
+        let received = reader.take(expected).read_to_end(&mut body).await? as u64;
+        if received < expected {
+            return Err(IncompleteBody { expected, received }.into_io_error().into());
+        }
+        return Ok(Body::from(body));
+    }
+    Ok(Body::from(read_to_close(reader, last).await?))
+}
+
+/// Whether the body of `res`, a response to `method`, is delimited by the connection closing.
+pub(crate) fn reads_to_close(method: Method, res: &Response) -> bool {
+    let status: u16 = res.status().into();
+    if method == Method::Head || matches!(status, 101 | 204 | 304) {
+        return false;
+    }
+    match transfer_codings(res).last() {
+        Some(coding) => coding != "chunked",
+        None => res.header(CONTENT_LENGTH).is_none(),
+    }
+}
+
+/// The transfer-codings of `res`, lowercased, in the order they were applied.
+pub(crate) fn transfer_codings(res: &Response) -> Vec<String> {
+    res.header(TRANSFER_ENCODING)
+        .into_iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.as_str().split(','))
+        .map(|coding| {
+            // Drop any transfer parameters.
+            let coding = coding.split(';').next().unwrap_or("");
+            coding.trim().to_ascii_lowercase()
+        })
+        .filter(|coding| !coding.is_empty())
+        .collect()
+}
+
+/// Undo a single transfer-coding other than chunked.
+fn decode(coding: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    let limit = MAX_BODY_LENGTH as u64 + 1;
+    let result = match coding {
+        "gzip" | "x-gzip" => GzDecoder::new(body).take(limit).read_to_end(&mut decoded),
+        "deflate" => ZlibDecoder::new(body).take(limit).read_to_end(&mut decoded),
+        "identity" => return Ok(body.to_vec()),
+        "chunked" => {
+            return Err(bad_gateway(
+                "chunked transfer-coding applied more than once, or not last",
+            ))
+        }
+        coding => {
+            return Err(Error::from_str(
+                StatusCode::BadGateway,
+                format!("unsupported transfer-coding '{}'", coding),
+            ))
+        }
+    };
+    result.map_err(|err| {
+        Error::from_str(
+            StatusCode::BadGateway,
+            format!("invalid {} transfer-coded body: {}", coding, err),
+        )
+    })?;
+    if decoded.len() > MAX_BODY_LENGTH {
+        return Err(bad_gateway("response body too long"));
+    }
+    Ok(decoded)
+}
+
+/// Read a body delimited by the connection closing, which only works for the last response on
+/// the connection.
+async fn read_to_close<R>(reader: &mut R, last: bool) -> Result<Vec<u8>, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    if !last {
+        return Err(bad_gateway(
+            "response is delimited by connection close, so the server does not support pipelining",
+        ));
+    }
+    let mut body = Vec::new();
+    reader
+        .take(MAX_BODY_LENGTH as u64 + 1)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() > MAX_BODY_LENGTH {
+        return Err(bad_gateway("response body too long"));
+    }
+    Ok(body)
+}
+
+/// Read a chunked body to the end, discarding chunk extensions and trailers.
+async fn read_chunked<R>(reader: &mut R) -> Result<Vec<u8>, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut body = Vec::new();
+    ChunkedDecoder::new(reader)
+        .take(MAX_BODY_LENGTH as u64 + 1)
+        .read_to_end(&mut body)
+        .await
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                Error::new(StatusCode::BadGateway, err)
+            }
+            _ => err.into(),
+        })?;
+    if body.len() > MAX_BODY_LENGTH {
+        return Err(bad_gateway("response body too long"));
+    }
+    Ok(body)
+}
+
+/// Whether the connection can't carry another request after this response, because the server
+/// will close it or has switched it to another protocol.
+pub(crate) fn closes_connection(res: &Response) -> bool {
+    if res.status() == StatusCode::SwitchingProtocols {
+        return true;
+    }
+
+    let has_token = |token: &str| {
+        res.header(CONNECTION)
+            .into_iter()
+            .flat_map(|values| values.iter())
+            .flat_map(|value| value.as_str().split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+
+    match res.version() {
+        Some(Version::Http1_0) => !has_token("keep-alive"),
+        _ => has_token("close"),
+    }
+}
+
+fn bad_gateway(msg: &'static str) -> Error {
+    Error::from_str(StatusCode::BadGateway, msg)
+}
@@ -0,0 +1,164 @@
+//! Exchanges of pre-serialized messages, bypassing request encoding and response decoding.
+
+use super::chunked::{chunk_size, MAX_SIZE_LINE_LENGTH};
+use super::length::{self, IncompleteBody};
+use super::parse::{parse_response_head, MAX_HEAD_LENGTH};
+use super::pipeline::{read_head, MAX_BODY_LENGTH};
+use crate::Error;
+
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite};
+use futures::io::{AsyncWriteExt, BufReader};
+use http_types::StatusCode;
+
+/// Write `bytes` to `stream` verbatim, then read one response back as the bytes it arrived as.
+///
+/// Interim `1xx` responses are kept, ahead of the final response. The final response ends where
+/// its framing says it does, or when the connection closes if it has no framing. Its body,
+/// framing included, fails past `MAX_BODY_LENGTH` bytes.
+pub(crate) async fn exchange<S>(stream: S, bytes: &[u8]) -> Result<Vec<u8>, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Responses to HEAD requests have no body, whatever their headers say.
+    let head_request = bytes.starts_with(b"HEAD ");
+    let mut stream = BufReader::new(stream);
+    stream.get_mut().write_all(bytes).await?;
+    stream.get_mut().flush().await?;
+
+    let mut raw = Vec::new();
+    loop {
+        let head = read_head(&mut stream).await?;
+        let (parsed, _) =
+            parse_response_head(&head).map_err(|err| Error::new(StatusCode::BadGateway, err))?;
+        raw.extend_from_slice(&head);
+
+        match parsed.status {
+            // The connection now speaks another protocol, so it's all part of the response.
+            101 => {}
+            100..=199 => continue,
+            204 | 304 => return Ok(raw),
+            _ if head_request => return Ok(raw),
+            _ => {}
+        }
+
+        let last_coding = header_values(&parsed.headers, "transfer-encoding")
+            .flat_map(|value| value.split(','))
+            .map(|coding| coding.split(';').next().unwrap_or("").trim())
+            .filter(|coding| !coding.is_empty())
+            .last();
+        let mut lengths = header_values(&parsed.headers, "content-length").peekable();
+
+        let start = raw.len();
+        let mut body = (&mut stream).take(MAX_BODY_LENGTH as u64 + 1);
+        let copied = if parsed.status == 101 || last_coding.is_some() {
+            if matches!(last_coding, Some(coding) if coding.eq_ignore_ascii_case("chunked")) {
+                copy_chunked(&mut body, &mut raw).await
+            } else {
+                copy_to_close(&mut body, &mut raw).await
+            }
+        } else if lengths.peek().is_some() {
+            let expected = length::parse(lengths)?;
+            if expected > MAX_BODY_LENGTH as u64 {
+                return Err(too_long());
+            }
+            let received = (&mut body).take(expected).read_to_end(&mut raw).await? as u64;
+            if received < expected {
+                return Err(IncompleteBody { expected, received }.into_io_error().into());
+            }
+            Ok(())
+        } else {
+            copy_to_close(&mut body, &mut raw).await
+        };
+        // Running out of the allowance shows up as a truncated body, so check for it first.
+        if raw.len() - start > MAX_BODY_LENGTH {
+            return Err(too_long());
+        }
+        return copied.map(|()| raw);
+    }
+}
+
+/// The values of every header called `name`.
+fn header_values<'a>(
+    headers: &'a [(String, String)],
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    headers
+        .iter()
+        .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Copy a chunked body verbatim, including chunk extensions and trailers.
+async fn copy_chunked<R>(reader: &mut R, raw: &mut Vec<u8>) -> Result<(), Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let size = chunk_size(copy_line(reader, raw, MAX_SIZE_LINE_LENGTH).await?)
+            .ok_or_else(|| Error::from_str(StatusCode::BadGateway, "invalid chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        // Read the data as it arrives, rather than allocating whatever size the server declares.
+        let received = (&mut *reader).take(size).read_to_end(raw).await? as u64;
+        if received < size {
+            return Err(closed());
+        }
+        if !copy_line(reader, raw, 0).await?.is_empty() {
+            return Err(Error::from_str(
+                StatusCode::BadGateway,
+                "missing line ending after chunk data",
+            ));
+        }
+    }
+
+    while !copy_line(reader, raw, MAX_HEAD_LENGTH).await?.is_empty() {}
+    Ok(())
+}
+
+/// Copy a single line of at most `limit` bytes onto the end of `raw`, returning it without its
+/// line ending.
+async fn copy_line<'a, R>(
+    reader: &mut R,
+    raw: &'a mut Vec<u8>,
+    limit: usize,
+) -> Result<&'a [u8], Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let start = raw.len();
+    // Leave room for the line ending.
+    (&mut *reader)
+        .take(limit as u64 + 2)
+        .read_until(b'\n', raw)
+        .await?;
+    let line = &raw[start..];
+    match line.strip_suffix(b"\n") {
+        Some(line) => Ok(line.strip_suffix(b"\r").unwrap_or(line)),
+        None if line.len() > limit => Err(Error::from_str(
+            StatusCode::BadGateway,
+            "chunked body line too long",
+        )),
+        None => Err(closed()),
+    }
+}
+
+/// Copy everything up to the end of the connection onto the end of `raw`.
+async fn copy_to_close<R>(reader: &mut R, raw: &mut Vec<u8>) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin,
+{
+    reader.read_to_end(raw).await?;
+    Ok(())
+}
+
+fn closed() -> Error {
+    Error::from_str(
+        StatusCode::BadGateway,
+        "connection closed in the middle of a chunked body",
+    )
+}
+
+fn too_long() -> Error {
+    Error::from_str(StatusCode::BadGateway, "response body too long")
+}
@@ -0,0 +1,112 @@
+//! Send a sequence of requests over one dedicated connection.
+
+use super::timeout::{self, FirstByte};
+use super::{pipeline, AsyncReadWrite, H1Client};
+use crate::transform;
+use crate::{bodyless, Error, Request, Response};
+
+use futures::io::BufReader;
+use futures::lock::Mutex;
+use http_types::url::Url;
+use http_types::StatusCode;
+use std::fmt;
+use std::net::SocketAddr;
+
+/// The session's connection, buffered for reading responses.
+type Conn = BufReader<FirstByte<Box<dyn AsyncReadWrite>>>;
+
+/// A single keep-alive connection to one origin, created by
+/// [`H1Client::session`](super::H1Client::session).
+///
+/// Every request sent through a session uses the same connection, for servers which keep
+/// per-connection state. Requests are sent one at a time, and each response body, of at most
+/// 256 MiB, is read into memory before `send` returns so that the connection is free for the
+/// next request. The
+/// connection is never used for anything else, and is closed when the session is dropped.
+///
+/// If the server closes the connection, a request fails, or a `send` is dropped before its
+/// response has been read, the session is closed and every later `send` returns an error; open
+/// a new session to continue.
+pub struct Session {
+    origin: Url,
+    conn: Mutex<Option<Conn>>,
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    /// The client the session was opened by, for its settings.
+    client: H1Client,
+}
+
+impl Session {
+    pub(crate) fn new(
+        origin: Url,
+        conn: Box<dyn AsyncReadWrite>,
+        peer_addr: Option<SocketAddr>,
+        local_addr: Option<SocketAddr>,
+        client: H1Client,
+    ) -> Self {
+        let conn = FirstByte::new(conn, client.first_byte_timeout);
+        Self {
+            origin,
+            conn: Mutex::new(Some(BufReader::new(conn))),
+            peer_addr,
+            local_addr,
+            client,
+        }
+    }
+
+    /// Send a request over the session's connection.
+    ///
+    /// The request must target the origin the session was created for.
+    pub async fn send(&self, mut req: Request) -> Result<Response, Error> {
+        if req.url().origin() != self.origin.origin() {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "session requests must share the session's origin",
+            ));
+        }
+        if self.client.strip_bodies {
+            bodyless::strip_body(&mut req);
+        }
+        if let Some(lang) = &self.client.accept_language {
+            lang.apply(&mut req);
+        }
+        req.set_peer_addr(self.peer_addr);
+        req.set_local_addr(self.local_addr);
+        let url = req.url().clone();
+        let method = req.method();
+
+        let mut conn = self.conn.lock().await;
+        // The connection is only put back once a complete response has been read from it, so a
+        // `send` which fails or is dropped partway leaves the session closed, rather than with
+        // the rest of a response waiting to be read as the answer to the next request.
+        let mut stream = conn
+            .take()
+            .ok_or_else(|| Error::from_str(StatusCode::BadGateway, "session is closed"))?;
+        stream.get_mut().rearm();
+        let mut res =
+            pipeline::exchange(&mut stream, req, self.client.default_content_type.as_ref())
+                .await
+                .map_err(timeout::map_error)?;
+        if !pipeline::closes_connection(&res) && !pipeline::reads_to_close(method, &res) {
+            *conn = Some(stream);
+        }
+        drop(conn);
+
+        if let Some(transform) = &self.client.transform {
+            transform::apply(transform.as_ref(), &mut res);
+        }
+        if let Some(cache) = &self.client.alt_svc {
+            cache.record(&url, &res);
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("origin", &self.origin.origin().ascii_serialization())
+            .field("peer_addr", &self.peer_addr)
+            .finish()
+    }
+}
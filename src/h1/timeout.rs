@@ -0,0 +1,128 @@
+//! Bound the time spent waiting for the first byte of a response.
+
+use crate::Error;
+
+use futures::io::{AsyncRead, AsyncWrite};
+use http_types::StatusCode;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The error returned when a server takes longer than the first-byte timeout to start
+/// responding.
+///
+/// The returned `Error` has a `504 Gateway Timeout` status, and can be told apart from other
+/// errors with `err.downcast_ref::<FirstByteTimeout>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirstByteTimeout {
+    /// The configured timeout.
+    pub timeout: Duration,
+}
+
+impl fmt::Display for FirstByteTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no response received within the first-byte timeout of {:?}",
+            self.timeout
+        )
+    }
+}
+
+impl std::error::Error for FirstByteTimeout {}
+
+/// A stream which fails reads with a `FirstByteTimeout` if nothing has been read once `timeout`
+/// has passed since the first read was attempted.
+///
+/// The h1 client only starts reading once the whole request has been written, so the timer
+/// covers the time between the request being sent and the first byte of the response. Streams
+/// which carry several requests are re-armed with `rearm` before each one.
+pub(crate) struct FirstByte<S> {
+    stream: S,
+    timeout: Option<Duration>,
+    /// Whether the timeout applies to reads, until one completes.
+    armed: bool,
+    // `Sync` because response bodies read from the stream must be.
+    timer: Option<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>,
+}
+
+impl<S> FirstByte<S> {
+    pub(crate) fn new(stream: S, timeout: Option<Duration>) -> Self {
+        Self {
+            stream,
+            timeout,
+            armed: true,
+            timer: None,
+        }
+    }
+
+    /// Apply the timeout again, to the response to the next request.
+    pub(crate) fn rearm(&mut self) {
+        self.armed = true;
+        self.timer = None;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FirstByte<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let timeout = match self.timeout {
+            Some(timeout) if self.armed => timeout,
+            _ => return Pin::new(&mut self.stream).poll_read(cx, buf),
+        };
+
+        if let Poll::Ready(read) = Pin::new(&mut self.stream).poll_read(cx, buf) {
+            self.armed = false;
+            self.timer = None;
+            return Poll::Ready(read);
+        }
+
+        let timer = self
+            .timer
+            .get_or_insert_with(|| Box::pin(async_std::task::sleep(timeout)));
+        match timer.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                FirstByteTimeout { timeout },
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FirstByte<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
+
+/// Turn the io error raised by `FirstByte` back into a `FirstByteTimeout` error.
+pub(crate) fn map_error(err: Error) -> Error {
+    let timeout = err
+        .downcast_ref::<io::Error>()
+        .and_then(|err| err.get_ref())
+        .and_then(|err| err.downcast_ref::<FirstByteTimeout>())
+        .copied();
+    match timeout {
+        Some(timeout) => Error::new(StatusCode::GatewayTimeout, timeout),
+        None => err,
+    }
+}
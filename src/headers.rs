@@ -0,0 +1,198 @@
+//! Header helpers for code built on top of an `HttpClient`.
+
+use crate::{Error, Request};
+
+use http_types::headers::{HeaderName, Headers, ACCEPT_LANGUAGE, CONNECTION};
+use http_types::StatusCode;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Hop-by-hop headers which always apply to a single connection only.
+const HOP_BY_HOP: [&str; 6] = [
+    "connection",
+    "keep-alive",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Remove hop-by-hop headers from a request or response before forwarding it.
+///
+/// This removes `Connection`, `Keep-Alive`, `TE`, `Trailer`, `Transfer-Encoding`, `Upgrade`, every
+/// `Proxy-*` header, and every header named in the `Connection` header.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::headers::strip_hop_by_hop;
+/// use http_types::{Method, Request};
+///
+/// let mut req = Request::new(Method::Get, "http://example.com/");
+/// req.insert_header("Connection", "X-Custom");
+/// req.insert_header("X-Custom", "1");
+/// strip_hop_by_hop(&mut req);
+/// assert!(req.header("X-Custom").is_none());
+/// ```
+pub fn strip_hop_by_hop(mut headers: impl AsMut<Headers>) {
+    let headers = headers.as_mut();
+
+    let mut names: Vec<HeaderName> = headers
+        .get(CONNECTION)
+        .into_iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.as_str().split(','))
+        .filter_map(|name| HeaderName::from_str(name.trim()).ok())
+        .collect();
+    names.extend(headers.names().filter(|name| is_hop_by_hop(name)).cloned());
+
+    for name in names {
+        headers.remove(name);
+    }
+}
+
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    let name = name.as_str();
+    HOP_BY_HOP.contains(&name) || name.starts_with("proxy-")
+}
+
+/// A validated `Accept-Language` header value, built from a list of language ranges and their
+/// quality values.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::headers::AcceptLanguage;
+///
+/// let lang = AcceptLanguage::new(vec![("en-US", 1.0), ("en", 0.8)]).unwrap();
+/// assert_eq!(lang.as_str(), "en-US,en;q=0.8");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptLanguage {
+    value: String,
+}
+
+impl AcceptLanguage {
+    /// Create a value from `(language range, quality)` pairs, in order of preference.
+    ///
+    /// Qualities must be between `0.0` and `1.0`, and are rounded to three decimal places. A
+    /// quality of `1.0` is left implicit. Language ranges must be `*`, or letters, digits and
+    /// hyphens.
+    pub fn new<S: AsRef<str>>(langs: impl IntoIterator<Item = (S, f32)>) -> Result<Self, Error> {
+        let mut value = String::new();
+        for (lang, quality) in langs {
+            let lang = lang.as_ref();
+            let valid = lang == "*"
+                || (!lang.is_empty()
+                    && lang.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'));
+            if !valid {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("invalid language range '{}'", lang),
+                ));
+            }
+            if !(0.0..=1.0).contains(&quality) {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("quality {} for '{}' is not between 0 and 1", quality, lang),
+                ));
+            }
+
+            if !value.is_empty() {
+                value.push(',');
+            }
+            value.push_str(lang);
+            let quality = format!("{:.3}", quality);
+            let quality = quality.trim_end_matches('0').trim_end_matches('.');
+            if quality != "1" {
+                // UNWRAP: writing to a `String` can't fail.
+                write!(value, ";q={}", quality).unwrap();
+            }
+        }
+
+        if value.is_empty() {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "Accept-Language needs at least one language",
+            ));
+        }
+        Ok(Self { value })
+    }
+
+    /// The header value.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Set the header on `req`, unless it already has an `Accept-Language` header.
+    pub fn apply(&self, req: &mut Request) {
+        if req.header(ACCEPT_LANGUAGE).is_none() {
+            req.insert_header(ACCEPT_LANGUAGE, self.value.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::headers::{CONTENT_TYPE, TE, TRAILER, TRANSFER_ENCODING, UPGRADE};
+    use http_types::{Method, Response};
+
+    #[test]
+    fn strips_connection_listed_and_standard_headers() {
+        let mut req = Request::new(Method::Get, "http://example.com/");
+        req.insert_header(CONNECTION, "keep-alive, X-Custom");
+        req.insert_header("X-Custom", "1");
+        req.insert_header("Keep-Alive", "timeout=5");
+        req.insert_header("Proxy-Authorization", "Basic Zm9vOmJhcg==");
+        req.insert_header(TE, "trailers");
+        req.insert_header(TRAILER, "Expires");
+        req.insert_header(TRANSFER_ENCODING, "chunked");
+        req.insert_header(UPGRADE, "websocket");
+        req.insert_header(CONTENT_TYPE, "text/plain");
+
+        strip_hop_by_hop(&mut req);
+
+        let names: Vec<_> = req.header_names().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["content-type"]);
+    }
+
+    #[test]
+    fn strips_response_headers() {
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header(CONNECTION, "close");
+        res.insert_header("Proxy-Authenticate", "Basic");
+
+        strip_hop_by_hop(&mut res);
+
+        assert_eq!(res.header_names().count(), 0);
+    }
+
+    #[test]
+    fn serializes_accept_language() -> Result<(), Error> {
+        let lang = AcceptLanguage::new(vec![("en-US".to_string(), 1.0), ("en".to_string(), 0.8)])?;
+        assert_eq!(lang.as_str(), "en-US,en;q=0.8");
+
+        let lang = AcceptLanguage::new(vec![("fr", 0.125), ("*", 0.0)])?;
+        assert_eq!(lang.as_str(), "fr;q=0.125,*;q=0");
+
+        let mut req = Request::new(Method::Get, "http://example.com/");
+        lang.apply(&mut req);
+        assert_eq!(req[ACCEPT_LANGUAGE], "fr;q=0.125,*;q=0");
+
+        let mut req = Request::new(Method::Get, "http://example.com/");
+        req.insert_header(ACCEPT_LANGUAGE, "de");
+        lang.apply(&mut req);
+        assert_eq!(req[ACCEPT_LANGUAGE], "de");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_accept_language() {
+        assert!(AcceptLanguage::new(vec![("en", 1.5)]).is_err());
+        assert!(AcceptLanguage::new(vec![("en", -0.1)]).is_err());
+        assert!(AcceptLanguage::new(vec![("en", f32::NAN)]).is_err());
+        assert!(AcceptLanguage::new(vec![("en US", 1.0)]).is_err());
+        assert!(AcceptLanguage::new(Vec::<(&str, f32)>::new()).is_err());
+    }
+}
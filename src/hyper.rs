@@ -1,29 +1,100 @@
 //! http-client implementation for reqwest
 
-use super::{Error, HttpClient, Request, Response};
+use super::alt_svc::AltSvcCache;
+use super::bodyless;
+use super::headers::AcceptLanguage;
+use super::transform::{self, BodyTransform};
+use super::{Capabilities, Error, HttpClient, Request, Response};
 use http_types::headers::{HeaderName, HeaderValue};
-use http_types::StatusCode;
+use http_types::{Mime, StatusCode};
 use hyper::body::HttpBody;
 use hyper_tls::HttpsConnector;
 use std::convert::TryFrom;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// Hyper-based HTTP Client.
 #[derive(Debug)]
-pub struct HyperClient {}
+pub struct HyperClient {
+    alt_svc: Option<AltSvcCache>,
+    transform: Option<Arc<dyn BodyTransform>>,
+    strip_bodies: bool,
+    accept_language: Option<AcceptLanguage>,
+    default_content_type: Option<Mime>,
+}
 
 impl HyperClient {
     /// Create a new client.
     ///
     /// There is no specific benefit to reusing instances of this client.
     pub fn new() -> Self {
-        HyperClient {}
+        HyperClient {
+            alt_svc: None,
+            transform: None,
+            strip_bodies: false,
+            accept_language: None,
+            default_content_type: None,
+        }
+    }
+
+    /// Record the `Alt-Svc` header of every response in `cache`.
+    pub fn with_alt_svc_cache(mut self, cache: AltSvcCache) -> Self {
+        self.alt_svc = Some(cache);
+        self
+    }
+
+    /// The cache `Alt-Svc` advertisements are recorded in, if any.
+    pub fn alt_svc_cache(&self) -> Option<&AltSvcCache> {
+        self.alt_svc.as_ref()
+    }
+
+    /// Apply `transform` to the body of every response.
+    pub fn with_body_transform(mut self, transform: impl BodyTransform) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Drop the body, and its `Content-Length`, from `GET`, `HEAD`, `DELETE`, `OPTIONS`, `TRACE`
+    /// and `CONNECT` requests instead of sending it.
+    ///
+    /// Disabled by default, in which case bodies are sent as-is.
+    pub fn strip_body_on_bodyless_methods(mut self, strip: bool) -> Self {
+        self.strip_bodies = strip;
+        self
+    }
+
+    /// Set `Accept-Language` to `lang` on every request which doesn't already have the header.
+    pub fn with_accept_language(mut self, lang: AcceptLanguage) -> Self {
+        self.accept_language = Some(lang);
+        self
+    }
+
+    /// Report `mime` as the content type of responses which arrive without a `Content-Type`,
+    /// rather than the `application/octet-stream` http-types gives them.
+    pub fn with_default_content_type(mut self, mime: Mime) -> Self {
+        self.default_content_type = Some(mime);
+        self
     }
 }
 
 impl HttpClient for HyperClient {
-    fn send(&self, req: Request) -> futures::future::BoxFuture<'static, Result<Response, Error>> {
+    fn send(
+        &self,
+        mut req: Request,
+    ) -> futures::future::BoxFuture<'static, Result<Response, Error>> {
+        let alt_svc = self.alt_svc.clone();
+        let transform = self.transform.clone();
+        let strip_bodies = self.strip_bodies;
+        let accept_language = self.accept_language.clone();
+        let default_content_type = self.default_content_type.clone();
         Box::pin(async move {
+            if strip_bodies {
+                bodyless::strip_body(&mut req);
+            }
+            if let Some(lang) = &accept_language {
+                lang.apply(&mut req);
+            }
+            let url = req.url().clone();
             let req = HyperHttpRequest::try_from(req).await?.into_inner();
             // UNWRAP: Scheme guaranteed to be "http" or "https" as part of conversion
             let scheme = req.uri().scheme_str().unwrap();
@@ -41,10 +112,27 @@ impl HttpClient for HyperClient {
                 _ => unreachable!(),
             }?;
 
-            let resp = HttpTypesResponse::try_from(response).await?.into_inner();
+            let typed = response.headers().contains_key(hyper::header::CONTENT_TYPE);
+            let mut resp = HttpTypesResponse::try_from(response).await?.into_inner();
+            if let Some(mime) = default_content_type.filter(|_| !typed) {
+                resp.set_content_type(mime);
+            }
+
+            if let Some(transform) = transform {
+                transform::apply(transform.as_ref(), &mut resp);
+            }
+            if let Some(cache) = alt_svc {
+                cache.record(&url, &resp);
+            }
             Ok(resp)
         })
     }
+
+    /// None of them. Requests are sent over HTTP/1.1 only, since neither hyper's default client
+    /// nor `hyper-tls` negotiate HTTP/2.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
 }
 
 struct HyperHttpRequest {
@@ -119,14 +207,17 @@ impl HttpTypesResponse {
         let mut res = Response::new(parts.status);
         res.set_version(Some(parts.version.into()));
 
+        // Repeated values of a header are yielded without a name, following the first value.
+        let mut current = None;
         for (name, value) in parts.headers {
             let value = value.as_bytes().to_owned();
             let value = HeaderValue::from_bytes(value)?;
 
             if let Some(name) = name {
-                let name = name.as_str();
-                let name = HeaderName::from_str(name)?;
-                res.insert_header(name, value);
+                current = Some(HeaderName::from_str(name.as_str())?);
+            }
+            if let Some(name) = &current {
+                res.append_header(name, value);
             }
         }
 
@@ -185,4 +276,95 @@ mod tests {
         assert!(client_res.is_ok());
         assert!(server_res.is_ok());
     }
+
+    async fn set_cookies(
+        _req: hyper::Request<hyper::Body>,
+    ) -> Result<hyper::Response<hyper::Body>, hyper::Error> {
+        Ok(hyper::Response::builder()
+            .header("Set-Cookie", "a=1")
+            .header("Set-Cookie", "b=2")
+            .body(hyper::Body::empty())
+            .unwrap())
+    }
+
+    #[tokio::test]
+    async fn duplicate_headers_are_preserved() {
+        let (send, recv) = channel::<()>();
+
+        let recv = async move { recv.await.unwrap_or(()) };
+
+        let addr = ([127, 0, 0, 1], portpicker::pick_unused_port().unwrap()).into();
+        let service = make_service_fn(|_| async { Ok::<_, hyper::Error>(service_fn(set_cookies)) });
+        let server = hyper::Server::bind(&addr)
+            .serve(service)
+            .with_graceful_shutdown(recv);
+
+        let client = HyperClient::new();
+        let url = Url::parse(&format!("http://localhost:{}", addr.port())).unwrap();
+        let req = Request::new(Method::Get, url);
+
+        let client = async move {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            let resp = client.send(req).await?;
+            send.send(()).unwrap();
+            let cookies: Vec<_> = resp["Set-Cookie"].iter().map(|v| v.as_str()).collect();
+            assert_eq!(cookies, ["a=1", "b=2"]);
+
+            Result::<(), Error>::Ok(())
+        };
+
+        let (client_res, server_res) = tokio::join!(client, server);
+        assert!(client_res.is_ok());
+        assert!(server_res.is_ok());
+    }
+
+    async fn untyped(
+        _req: hyper::Request<hyper::Body>,
+    ) -> Result<hyper::Response<hyper::Body>, hyper::Error> {
+        Ok(hyper::Response::new(hyper::Body::from("{}")))
+    }
+
+    #[tokio::test]
+    async fn applies_default_content_type() {
+        let (send, recv) = channel::<()>();
+
+        let recv = async move { recv.await.unwrap_or(()) };
+
+        let addr = ([127, 0, 0, 1], portpicker::pick_unused_port().unwrap()).into();
+        let service = make_service_fn(|_| async { Ok::<_, hyper::Error>(service_fn(untyped)) });
+        let server = hyper::Server::bind(&addr)
+            .serve(service)
+            .with_graceful_shutdown(recv);
+
+        let client = HyperClient::new().with_default_content_type(http_types::mime::JSON);
+        let url = Url::parse(&format!("http://localhost:{}", addr.port())).unwrap();
+        let req = Request::new(Method::Get, url);
+
+        let client = async move {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            let resp = client.send(req).await?;
+            send.send(()).unwrap();
+            assert_eq!(resp.content_type(), Some(http_types::mime::JSON));
+
+            Result::<(), Error>::Ok(())
+        };
+
+        let (client_res, server_res) = tokio::join!(client, server);
+        assert!(client_res.is_ok());
+        assert!(server_res.is_ok());
+    }
+
+    #[test]
+    fn reports_capabilities() {
+        assert_eq!(
+            HyperClient::new().capabilities(),
+            crate::Capabilities {
+                http2: false,
+                unix_sockets: false,
+                local_address_binding: false,
+                metrics: false,
+                tls_client_auth: false,
+            }
+        );
+    }
 }
@@ -1,9 +1,15 @@
 //! http-client implementation for isahc
 
-use super::{Body, Error, HttpClient, Request, Response};
+use super::alt_svc::AltSvcCache;
+use super::bodyless;
+use super::headers::AcceptLanguage;
+use super::transform::{self, BodyTransform};
+use super::{Capabilities, Error, HttpClient, Request, Response};
 
-use async_std::io::BufReader;
 use futures::future::BoxFuture;
+use http_types::url::Url;
+use http_types::{Mime, StatusCode};
+use isahc::config::Configurable;
 use isahc::http;
 use std::sync::Arc;
 
@@ -11,6 +17,13 @@ use std::sync::Arc;
 #[derive(Debug)]
 pub struct IsahcClient {
     client: Arc<isahc::HttpClient>,
+    alt_svc: Option<AltSvcCache>,
+    transform: Option<Arc<dyn BodyTransform>>,
+    socks_proxy: Option<http::Uri>,
+    strip_bodies: bool,
+    accept_language: Option<AcceptLanguage>,
+    default_content_type: Option<Mime>,
+    capabilities: Capabilities,
 }
 
 impl Default for IsahcClient {
@@ -29,14 +42,99 @@ impl IsahcClient {
     pub fn from_client(client: isahc::HttpClient) -> Self {
         Self {
             client: Arc::new(client),
+            alt_svc: None,
+            transform: None,
+            socks_proxy: None,
+            strip_bodies: false,
+            accept_language: None,
+            default_content_type: None,
+            capabilities: Capabilities {
+                http2: true,
+                ..Capabilities::default()
+            },
         }
     }
+
+    /// Record the `Alt-Svc` header of every response in `cache`.
+    pub fn with_alt_svc_cache(mut self, cache: AltSvcCache) -> Self {
+        self.alt_svc = Some(cache);
+        self
+    }
+
+    /// The cache `Alt-Svc` advertisements are recorded in, if any.
+    pub fn alt_svc_cache(&self) -> Option<&AltSvcCache> {
+        self.alt_svc.as_ref()
+    }
+
+    /// Apply `transform` to the body of every response, after isahc's automatic decompression.
+    pub fn with_body_transform(mut self, transform: impl BodyTransform) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Connect through the SOCKS5 proxy at `proxy`, overriding any proxy configured on the
+    /// underlying isahc client.
+    ///
+    /// The proxy URL must use the `socks5` or `socks5h` scheme, and may carry a username and
+    /// password. With `socks5h` the proxy resolves the hostnames of requests, otherwise they're
+    /// resolved locally.
+    pub fn with_socks_proxy(mut self, proxy: Url) -> Result<Self, Error> {
+        match proxy.scheme() {
+            "socks5" | "socks5h" => {}
+            scheme => {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("invalid SOCKS proxy scheme '{}'", scheme),
+                ))
+            }
+        }
+        self.socks_proxy = Some(proxy.as_str().parse()?);
+        Ok(self)
+    }
+
+    /// Drop the body, and its `Content-Length`, from `GET`, `HEAD`, `DELETE`, `OPTIONS`, `TRACE`
+    /// and `CONNECT` requests instead of sending it.
+    ///
+    /// Disabled by default, in which case bodies are sent as-is.
+    pub fn strip_body_on_bodyless_methods(mut self, strip: bool) -> Self {
+        self.strip_bodies = strip;
+        self
+    }
+
+    /// Set `Accept-Language` to `lang` on every request which doesn't already have the header.
+    pub fn with_accept_language(mut self, lang: AcceptLanguage) -> Self {
+        self.accept_language = Some(lang);
+        self
+    }
+
+    /// Set `Content-Type` to `mime` on responses which arrive without one, in place of the
+    /// `application/octet-stream` they'd otherwise report.
+    pub fn with_default_content_type(mut self, mime: Mime) -> Self {
+        self.default_content_type = Some(mime);
+        self
+    }
+
+    /// Report `capabilities` from [`HttpClient::capabilities`], for the features configured on
+    /// the isahc client passed to [`from_client`](IsahcClient::from_client).
+    ///
+    /// isahc doesn't say how a client was configured, so only HTTP/2 is reported by default.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
 }
 
 impl Clone for IsahcClient {
     fn clone(&self) -> Self {
         Self {
             client: self.client.clone(),
+            alt_svc: self.alt_svc.clone(),
+            transform: self.transform.clone(),
+            socks_proxy: self.socks_proxy.clone(),
+            strip_bodies: self.strip_bodies,
+            accept_language: self.accept_language.clone(),
+            default_content_type: self.default_content_type.clone(),
+            capabilities: self.capabilities,
         }
     }
 }
@@ -44,17 +142,33 @@ impl Clone for IsahcClient {
 impl HttpClient for IsahcClient {
     fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
         let client = self.client.clone();
+        let alt_svc = self.alt_svc.clone();
+        let transform = self.transform.clone();
+        let socks_proxy = self.socks_proxy.clone();
+        let strip_bodies = self.strip_bodies;
+        let accept_language = self.accept_language.clone();
+        let default_content_type = self.default_content_type.clone();
         Box::pin(async move {
+            if strip_bodies {
+                bodyless::strip_body(&mut req);
+            }
+            if let Some(lang) = &accept_language {
+                lang.apply(&mut req);
+            }
             let mut builder = http::Request::builder()
                 .uri(req.url().as_str())
                 .method(http::Method::from_bytes(req.method().to_string().as_bytes()).unwrap());
 
-            for name in req.header_names() {
-                if let Some(value) = req.header(name) {
+            for (name, values) in &req {
+                for value in values {
                     builder = builder.header(name.as_str(), value.as_str());
                 }
             }
 
+            if let Some(proxy) = socks_proxy {
+                builder = builder.proxy(Some(proxy));
+            }
+
             let body = req.take_body();
 
             let body = match body.len() {
@@ -65,16 +179,49 @@ impl HttpClient for IsahcClient {
             let request = builder.body(body).unwrap();
             let res = client.send_async(request).await.map_err(Error::from)?;
             let (parts, body) = res.into_parts();
-            let len = body.len().map(|len| len as usize);
-            let body = Body::from_reader(BufReader::new(body), len);
+            // A `Content-Length` on an encoded response describes the compressed bytes, not what
+            // the decompressed body will yield, so only trust it for unencoded responses.
+            let len = if is_content_encoded(&parts.headers) {
+                None
+            } else {
+                body.len().map(|len| len as usize)
+            };
+            let body = crate::body::from_reader(body, len);
             let mut response = http_types::Response::new(parts.status.as_u16());
             for (name, value) in &parts.headers {
-                response.insert_header(name.as_str(), value.to_str().unwrap());
+                response.append_header(name.as_str(), value.to_str().unwrap());
+            }
+            // Before the body is set, since that fills in a missing `Content-Type` with its own.
+            if let Some(mime) = default_content_type {
+                if !parts.headers.contains_key(http::header::CONTENT_TYPE) {
+                    response.set_content_type(mime);
+                }
             }
             response.set_body(body);
+
+            if let Some(transform) = transform {
+                transform::apply(transform.as_ref(), &mut response);
+            }
+            if let Some(cache) = alt_svc {
+                cache.record(req.url(), &response);
+            }
             Ok(response)
         })
     }
+
+    /// HTTP/2, which curl negotiates over TLS, plus anything declared with
+    /// [`with_capabilities`](IsahcClient::with_capabilities).
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
+
+/// Whether the response body carries a content-coding other than `identity`.
+fn is_content_encoded(headers: &http::HeaderMap) -> bool {
+    headers
+        .get_all(http::header::CONTENT_ENCODING)
+        .iter()
+        .any(|value| value != "identity")
 }
 
 #[cfg(test)]
@@ -121,4 +268,169 @@ mod tests {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn compressed_body_has_unknown_len() -> Result<()> {
+        // "hello world", gzip-compressed.
+        const GZIPPED: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85, 0x11, 0x4a, 0x0d, 0x0b,
+            0x00, 0x00, 0x00,
+        ];
+
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok)
+                .set_header("Content-Encoding", "gzip");
+            response.set_body(GZIPPED.to_vec());
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let request = Request::new(http_types::Method::Get, url);
+            let response: Response = IsahcClient::new().send(request).await?;
+            assert_eq!(response.len(), None);
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn duplicate_headers_are_preserved() -> Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async move {
+            Ok(tide::Response::new(http_types::StatusCode::Ok)
+                .append_header("Set-Cookie", "a=1")
+                .append_header("Set-Cookie", "b=2"))
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let request = Request::new(http_types::Method::Get, url);
+            let response: Response = IsahcClient::new().send(request).await?;
+            let cookies: Vec<_> = response["Set-Cookie"].iter().map(|v| v.as_str()).collect();
+            assert_eq!(cookies, ["a=1", "b=2"]);
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_capabilities() {
+        assert_eq!(
+            IsahcClient::new().capabilities(),
+            crate::Capabilities {
+                http2: true,
+                unix_sockets: false,
+                local_address_binding: false,
+                metrics: false,
+                tls_client_auth: false,
+            }
+        );
+
+        let unix = crate::Capabilities {
+            http2: true,
+            unix_sockets: true,
+            ..crate::Capabilities::default()
+        };
+        let client = IsahcClient::new().with_capabilities(unix);
+        assert_eq!(client.capabilities(), unix);
+    }
+
+    #[async_std::test]
+    async fn applies_default_content_type() -> Result<()> {
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let _server: task::JoinHandle<Result<()>> = task::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut reader = async_std::io::BufReader::new(stream.clone());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).await?;
+            }
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}")
+                .await?;
+            Ok(())
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let client = IsahcClient::new().with_default_content_type(http_types::mime::JSON);
+        let res = client
+            .send(Request::new(http_types::Method::Get, url))
+            .await?;
+        assert_eq!(res.content_type(), Some(http_types::mime::JSON));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn discarded_body_returns_connection_to_pool() -> Result<()> {
+        use crate::ResponseExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = async_std::net::TcpListener::bind(("localhost", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        // Answer any number of requests on each connection, counting connections.
+        let counter = accepted.clone();
+        let _server: task::JoinHandle<Result<()>> = task::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await?;
+                counter.fetch_add(1, Ordering::SeqCst);
+                task::spawn(async move {
+                    let mut reader = async_std::io::BufReader::new(stream.clone());
+                    let mut stream = stream;
+                    let body = vec![b'a'; 256 * 1024];
+                    loop {
+                        let mut line = String::new();
+                        while line != "\r\n" {
+                            line.clear();
+                            if reader.read_line(&mut line).await? == 0 {
+                                return Result::Ok(());
+                            }
+                        }
+                        let head =
+                            format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len());
+                        stream.write_all(head.as_bytes()).await?;
+                        stream.write_all(&body).await?;
+                    }
+                });
+            }
+        });
+
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let client = IsahcClient::new();
+        for _ in 0..3 {
+            let res = client
+                .send(Request::new(http_types::Method::Get, url.clone()))
+                .await?;
+            res.discard_body().await?;
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
 }
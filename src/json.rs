@@ -0,0 +1,162 @@
+//! Read newline-delimited JSON (ND-JSON) response bodies.
+
+use crate::{Body, Error};
+
+use futures::io::{AsyncBufReadExt, Lines};
+use futures::stream::Stream;
+use http_types::StatusCode;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A stream of the values in a newline-delimited JSON body, created by
+/// [`ResponseExt::json_lines`](crate::ResponseExt::json_lines).
+///
+/// Each non-empty line is deserialized into a `T`. A line that fails to deserialize is yielded
+/// as an error with a `422 Unprocessable Entity` status, and by default the stream then moves
+/// on to the next line. Errors reading the body always end the stream.
+pub struct JsonLines<T> {
+    lines: Lines<Body>,
+    stop_on_error: bool,
+    done: bool,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonLines<T> {
+    pub(crate) fn new(body: Body) -> Self {
+        Self {
+            lines: body.lines(),
+            stop_on_error: false,
+            done: false,
+            _value: PhantomData,
+        }
+    }
+
+    /// End the stream after the first line that fails to deserialize, instead of continuing.
+    pub fn stop_on_error(mut self) -> Self {
+        self.stop_on_error = true;
+        self
+    }
+}
+
+impl<T> fmt::Debug for JsonLines<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLines")
+            .field("stop_on_error", &self.stop_on_error)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<T: DeserializeOwned> Stream for JsonLines<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        loop {
+            let line = match futures::ready!(Pin::new(&mut self.lines).poll_next(cx)) {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                None => {
+                    self.done = true;
+                    return Poll::Ready(None);
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return match serde_json::from_str(&line) {
+                Ok(value) => Poll::Ready(Some(Ok(value))),
+                Err(err) => {
+                    self.done = self.stop_on_error;
+                    Poll::Ready(Some(Err(Error::new(StatusCode::UnprocessableEntity, err))))
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Response, ResponseExt};
+    use futures::stream::StreamExt;
+    use http_types::StatusCode;
+    use serde::de::{Deserialize, Deserializer, Error as _};
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Event {
+        kind: String,
+        id: u64,
+    }
+
+    // `#[derive(Deserialize)]` is incompatible with the crate's `forbid(rust_2018_idioms)`.
+    impl<'de> Deserialize<'de> for Event {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut fields = BTreeMap::<String, Value>::deserialize(deserializer)?;
+            let mut field = |name| {
+                fields
+                    .remove(name)
+                    .ok_or_else(|| D::Error::missing_field(name))
+            };
+            Ok(Event {
+                kind: serde_json::from_value(field("kind")?).map_err(D::Error::custom)?,
+                id: serde_json::from_value(field("id")?).map_err(D::Error::custom)?,
+            })
+        }
+    }
+
+    fn event(kind: &str, id: u64) -> Event {
+        Event {
+            kind: kind.to_string(),
+            id,
+        }
+    }
+
+    fn response(body: &str) -> Response {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(body);
+        res
+    }
+
+    #[async_std::test]
+    async fn reads_each_line() {
+        let res = response(
+            "{\"kind\":\"start\",\"id\":1}\n{\"kind\":\"data\",\"id\":2}\r\n\n{\"kind\":\"end\",\"id\":3}",
+        );
+        let events: Vec<Event> = res.json_lines().map(|event| event.unwrap()).collect().await;
+        assert_eq!(
+            events,
+            [event("start", 1), event("data", 2), event("end", 3)]
+        );
+    }
+
+    #[async_std::test]
+    async fn continues_or_stops_after_invalid_lines() {
+        let body = "{\"kind\":\"start\",\"id\":1}\nnot json\n{\"kind\":\"end\",\"id\":3}\n";
+
+        let results: Vec<_> = response(body).json_lines::<Event>().collect().await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().status(),
+            StatusCode::UnprocessableEntity
+        );
+        assert_eq!(results[2].as_ref().unwrap(), &event("end", 3));
+
+        let results: Vec<_> = response(body)
+            .json_lines::<Event>()
+            .stop_on_error()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results[1].is_err());
+    }
+}
@@ -36,6 +36,57 @@ pub mod h1;
 #[cfg(feature = "hyper_client")]
 pub mod hyper;
 
+pub mod alt_svc;
+pub mod balance;
+pub mod body;
+pub mod builder;
+pub mod headers;
+pub mod limit;
+pub mod progress;
+pub mod redirect;
+pub mod retry;
+pub mod transform;
+
+#[cfg_attr(feature = "docs", doc(cfg(digest)))]
+#[cfg(feature = "digest")]
+pub mod checksum;
+
+#[cfg_attr(feature = "docs", doc(cfg(digest_auth)))]
+#[cfg(feature = "digest_auth")]
+pub mod digest_auth;
+
+#[cfg_attr(feature = "docs", doc(cfg(json)))]
+#[cfg(feature = "json")]
+pub mod json;
+
+#[cfg_attr(feature = "docs", doc(cfg(opentelemetry)))]
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+
+#[cfg_attr(feature = "docs", doc(cfg(query)))]
+#[cfg(feature = "query")]
+pub mod query;
+
+#[cfg_attr(feature = "docs", doc(cfg(request_id)))]
+#[cfg(feature = "request_id")]
+pub mod request_id;
+
+#[cfg_attr(feature = "docs", doc(cfg(feature = "test-util")))]
+#[cfg(feature = "test-util")]
+pub mod test_support;
+
+#[cfg_attr(feature = "docs", doc(cfg(tower)))]
+#[cfg(feature = "tower")]
+pub mod tower;
+
+mod bodyless;
+mod buffered;
+mod replay;
+mod response_ext;
+
+pub use buffered::BufferedResponse;
+pub use response_ext::ResponseExt;
+
 /// An HTTP Request type with a streaming body.
 pub type Request = http_types::Request;
 
@@ -59,6 +110,51 @@ pub use http_types;
 pub trait HttpClient: std::fmt::Debug + Unpin + Send + Sync + 'static {
     /// Perform a request.
     fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>>;
+
+    /// The features this backend supports, so generic code can refuse configuration which would
+    /// otherwise be silently ignored.
+    ///
+    /// Defaults to supporting none of them.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// The optional features an `HttpClient` backend supports.
+///
+/// A feature counts as supported when the backend provides it as configured. For the h1
+/// backend that includes whatever a custom connector reports through
+#[cfg_attr(
+    feature = "h1_client",
+    doc = "[`Connector::capabilities`](h1::Connector::capabilities)."
+)]
+#[cfg_attr(not(feature = "h1_client"), doc = "`Connector::capabilities`.")]
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::HttpClient;
+///
+/// let client = H1Client::new();
+/// assert!(!client.capabilities().http2);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Requests can be sent over HTTP/2.
+    pub http2: bool,
+    /// Requests can be sent over Unix domain sockets.
+    pub unix_sockets: bool,
+    /// Connections can be bound to a chosen local address or interface.
+    pub local_address_binding: bool,
+    /// Transfer metrics, such as timings, can be collected.
+    pub metrics: bool,
+    /// TLS client certificates can be presented.
+    pub tls_client_auth: bool,
 }
 
 /// The raw body of an http request or response.
@@ -0,0 +1,532 @@
+//! Cap the number of requests a client has in flight.
+
+use crate::{Body, Capabilities, Error, HttpClient, Request, Response};
+
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::io::{AsyncBufRead, AsyncRead};
+use futures::lock::Mutex;
+use futures::stream::StreamExt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A client which allows at most a fixed number of requests to be in flight at once, waiting
+/// for a slot before sending any request beyond that.
+///
+/// A request stays in flight until its response body has been read to the end or dropped, not
+/// just until the response head arrives, so slow body reads count against the limit too.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::limit::InFlightLimit;
+///
+/// let client = InFlightLimit::new(H1Client::new(), 4);
+/// assert_eq!(client.in_flight(), 0);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct InFlightLimit<C> {
+    client: Arc<C>,
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl<C: HttpClient> InFlightLimit<C> {
+    /// Wrap `client`, allowing at most `max` requests in flight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn new(client: C, max: usize) -> Self {
+        assert!(max > 0, "the in-flight limit must be at least 1");
+        Self {
+            client: Arc::new(client),
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// The most requests allowed in flight at once.
+    pub fn max_in_flight(&self) -> usize {
+        self.max
+    }
+
+    /// The number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.semaphore.acquired()
+    }
+}
+
+impl<C: HttpClient> HttpClient for InFlightLimit<C> {
+    fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let client = self.client.clone();
+        let semaphore = self.semaphore.clone();
+        Box::pin(async move {
+            let permit = semaphore.acquire().await;
+            let mut res = client.send(req).await?;
+            PermitBody::hold(&mut res, permit);
+            Ok(res)
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// A client which limits how many requests are in flight at once like [`InFlightLimit`], but
+/// finds the limit itself by additive increase and multiplicative decrease, the way TCP
+/// congestion control finds a window size.
+///
+/// The limit starts at the minimum, and grows by one each time as many requests as the limit
+/// succeed in a row. It halves, down to the minimum, whenever a request fails: when it returns
+/// an error, a `429 Too Many Requests` or `5xx` response, or a response head which took longer
+/// than the latency threshold, 1 second by default. A smaller limit takes effect as requests
+/// in flight complete.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::limit::AdaptiveConcurrencyClient;
+/// use std::time::Duration;
+///
+/// let client = AdaptiveConcurrencyClient::new(H1Client::new(), 2, 64)
+///     .with_latency_threshold(Duration::from_millis(250));
+/// assert_eq!(client.limit(), 2);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AdaptiveConcurrencyClient<C> {
+    client: Arc<C>,
+    semaphore: Arc<Semaphore>,
+    control: Arc<std::sync::Mutex<Control>>,
+    latency_threshold: Duration,
+}
+
+#[derive(Debug)]
+struct Control {
+    limit: usize,
+    min: usize,
+    max: usize,
+    /// Requests which have succeeded since the limit last changed.
+    successes: usize,
+}
+
+impl Control {
+    /// Adjust the limit after a request, resizing `semaphore` to match.
+    fn record(&mut self, succeeded: bool, semaphore: &Semaphore) {
+        if succeeded {
+            self.successes += 1;
+            if self.successes >= self.limit {
+                self.successes = 0;
+                if self.limit < self.max {
+                    self.limit += 1;
+                    semaphore.add_permit();
+                }
+            }
+        } else {
+            self.successes = 0;
+            let limit = (self.limit / 2).max(self.min);
+            for _ in limit..self.limit {
+                semaphore.remove_permit();
+            }
+            if limit < self.limit {
+                log::debug!("lowering concurrency limit to {}", limit);
+            }
+            self.limit = limit;
+        }
+    }
+}
+
+impl<C: HttpClient> AdaptiveConcurrencyClient<C> {
+    /// Wrap `client`, keeping the limit between `min` and `max` requests in flight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is zero or greater than `max`.
+    pub fn new(client: C, min: usize, max: usize) -> Self {
+        assert!(min > 0, "the minimum concurrency limit must be at least 1");
+        assert!(
+            min <= max,
+            "the minimum concurrency limit exceeds the maximum"
+        );
+        Self {
+            client: Arc::new(client),
+            semaphore: Arc::new(Semaphore::new(min)),
+            control: Arc::new(std::sync::Mutex::new(Control {
+                limit: min,
+                min,
+                max,
+                successes: 0,
+            })),
+            latency_threshold: Duration::from_secs(1),
+        }
+    }
+
+    /// Count a request as failed when its response head takes longer than `threshold`.
+    pub fn with_latency_threshold(mut self, threshold: Duration) -> Self {
+        self.latency_threshold = threshold;
+        self
+    }
+
+    /// The current limit on requests in flight.
+    pub fn limit(&self) -> usize {
+        self.control.lock().unwrap().limit
+    }
+
+    /// The number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.semaphore.acquired()
+    }
+}
+
+impl<C: HttpClient> HttpClient for AdaptiveConcurrencyClient<C> {
+    fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let client = self.client.clone();
+        let semaphore = self.semaphore.clone();
+        let control = self.control.clone();
+        let latency_threshold = self.latency_threshold;
+        Box::pin(async move {
+            let permit = semaphore.acquire().await;
+            let start = Instant::now();
+            let res = client.send(req).await;
+            let succeeded = match &res {
+                Ok(res) => {
+                    let status: u16 = res.status().into();
+                    status != 429 && status < 500 && start.elapsed() <= latency_threshold
+                }
+                Err(_) => false,
+            };
+            control.lock().unwrap().record(succeeded, &semaphore);
+
+            let mut res = res?;
+            PermitBody::hold(&mut res, permit);
+            Ok(res)
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// A counting semaphore whose permits are tokens in a channel.
+#[derive(Debug)]
+pub(crate) struct Semaphore {
+    tokens: Mutex<mpsc::UnboundedReceiver<()>>,
+    release: mpsc::UnboundedSender<()>,
+    acquired: AtomicUsize,
+    /// Permits to withhold as they're returned, after the semaphore was shrunk.
+    debt: AtomicUsize,
+}
+
+impl Semaphore {
+    pub(crate) fn new(permits: usize) -> Self {
+        let (release, tokens) = mpsc::unbounded();
+        for _ in 0..permits {
+            // UNWRAP: the receiver is alive.
+            release.unbounded_send(()).unwrap();
+        }
+        Self {
+            tokens: Mutex::new(tokens),
+            release,
+            acquired: AtomicUsize::new(0),
+            debt: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a permit, which is returned when it's dropped.
+    pub(crate) async fn acquire(self: &Arc<Self>) -> Permit {
+        let mut tokens = self.tokens.lock().await;
+        loop {
+            // UNWRAP: the semaphore holds a sender, so the channel never closes.
+            tokens.next().await.unwrap();
+            // Discard the tokens of removed permits.
+            if !self.repay() {
+                break;
+            }
+        }
+        self.acquired.fetch_add(1, Ordering::SeqCst);
+        Permit {
+            semaphore: self.clone(),
+        }
+    }
+
+    /// The number of permits currently held.
+    pub(crate) fn acquired(&self) -> usize {
+        self.acquired.load(Ordering::SeqCst)
+    }
+
+    /// Allow one more permit to be held at once.
+    pub(crate) fn add_permit(&self) {
+        if !self.repay() {
+            // UNWRAP: the receiver lives as long as the semaphore.
+            self.release.unbounded_send(()).unwrap();
+        }
+    }
+
+    /// Allow one fewer permit to be held at once, once a permit is next returned.
+    pub(crate) fn remove_permit(&self) {
+        self.debt.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Cancel one withheld permit, if any are owed.
+    fn repay(&self) -> bool {
+        self.debt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |debt| {
+                debt.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
+
+/// A permit from a `Semaphore`, returned on drop.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.semaphore.acquired.fetch_sub(1, Ordering::SeqCst);
+        if !self.semaphore.repay() {
+            // UNWRAP: the receiver lives as long as the semaphore, which we hold.
+            self.semaphore.release.unbounded_send(()).unwrap();
+        }
+    }
+}
+
+/// A body which holds a permit until it has been read to the end or dropped.
+struct PermitBody {
+    inner: Option<(Body, Permit)>,
+}
+
+impl PermitBody {
+    /// Hold `permit` until the body of `res` has been read to the end or dropped.
+    fn hold(res: &mut Response, permit: Permit) {
+        let len = res.len();
+        let body = Self {
+            inner: Some((res.take_body(), permit)),
+        };
+        res.set_body(Body::from_reader(body, len));
+    }
+}
+
+impl AsyncRead for PermitBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = match &mut self.inner {
+            Some((body, _)) => futures::ready!(Pin::new(body).poll_read(cx, buf))?,
+            None => return Poll::Ready(Ok(0)),
+        };
+        if read == 0 && !buf.is_empty() {
+            // Drop the body before the permit, so its resources are released first.
+            self.inner = None;
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl AsyncBufRead for PermitBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        let available = match &mut this.inner {
+            Some((body, _)) => futures::ready!(Pin::new(body).poll_fill_buf(cx))?.len(),
+            None => return Poll::Ready(Ok(&[])),
+        };
+        if available == 0 {
+            this.inner = None;
+            return Poll::Ready(Ok(&[]));
+        }
+        // UNWRAP: `inner` was just checked to be present. Filling again returns the buffer that
+        // was just filled, without reading.
+        let (body, _) = this.inner.as_mut().unwrap();
+        Pin::new(body).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        if let Some((body, _)) = &mut self.inner {
+            Pin::new(body).consume(amt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use http_types::{Method, StatusCode};
+    use std::sync::atomic::AtomicBool;
+
+    /// Counts how many responses are outstanding, from the request being sent until its body
+    /// is dropped.
+    #[derive(Debug, Clone, Default)]
+    struct Tracker {
+        active: Arc<AtomicUsize>,
+        max: Arc<AtomicUsize>,
+    }
+
+    struct Guard {
+        body: futures::io::Cursor<&'static [u8]>,
+        active: Arc<AtomicUsize>,
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl AsyncRead for Guard {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.body).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncBufRead for Guard {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            Pin::new(&mut self.get_mut().body).poll_fill_buf(cx)
+        }
+
+        fn consume(mut self: Pin<&mut Self>, amt: usize) {
+            Pin::new(&mut self.body).consume(amt)
+        }
+    }
+
+    impl HttpClient for Tracker {
+        fn send(&self, _req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let active = self.active.clone();
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max.fetch_max(now, Ordering::SeqCst);
+            Box::pin(async move {
+                task::sleep(Duration::from_millis(10)).await;
+                let mut res = Response::new(StatusCode::Ok);
+                let body = Guard {
+                    body: futures::io::Cursor::new(&b"hello"[..]),
+                    active,
+                };
+                res.set_body(Body::from_reader(body, Some(5)));
+                Ok(res)
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn holds_permits_until_bodies_are_read() -> Result<(), Error> {
+        let tracker = Tracker::default();
+        let client = Arc::new(InFlightLimit::new(tracker.clone(), 2));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let client = client.clone();
+                task::spawn(async move {
+                    let req = Request::new(Method::Get, "http://example.com/");
+                    let mut res = client.send(req).await?;
+                    // Read the body slowly, after the head has arrived.
+                    task::sleep(Duration::from_millis(20)).await;
+                    assert_eq!(res.body_string().await?, "hello");
+                    Result::<(), Error>::Ok(())
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await?;
+        }
+
+        assert_eq!(tracker.max.load(Ordering::SeqCst), 2);
+        assert_eq!(client.in_flight(), 0);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn dropping_a_response_releases_its_permit() -> Result<(), Error> {
+        let client = InFlightLimit::new(Tracker::default(), 1);
+        let res = client
+            .send(Request::new(Method::Get, "http://example.com/"))
+            .await?;
+        assert_eq!(client.in_flight(), 1);
+        drop(res);
+        assert_eq!(client.in_flight(), 0);
+        Ok(())
+    }
+
+    /// Answers after a short delay, with `503 Service Unavailable` while `failing` is set,
+    /// tracking the most requests it has had outstanding at once.
+    #[derive(Debug, Clone, Default)]
+    struct Upstream {
+        failing: Arc<AtomicBool>,
+        active: Arc<AtomicUsize>,
+        max: Arc<AtomicUsize>,
+    }
+
+    impl HttpClient for Upstream {
+        fn send(&self, _req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let this = self.clone();
+            let now = this.active.fetch_add(1, Ordering::SeqCst) + 1;
+            this.max.fetch_max(now, Ordering::SeqCst);
+            Box::pin(async move {
+                task::sleep(Duration::from_millis(2)).await;
+                this.active.fetch_sub(1, Ordering::SeqCst);
+                if this.failing.load(Ordering::SeqCst) {
+                    Ok(Response::new(StatusCode::ServiceUnavailable))
+                } else {
+                    Ok(Response::new(StatusCode::Ok))
+                }
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn adapts_limit_to_upstream_health() -> Result<(), Error> {
+        let upstream = Upstream::default();
+        let client = Arc::new(AdaptiveConcurrencyClient::new(upstream.clone(), 1, 8));
+        let send = |client: Arc<AdaptiveConcurrencyClient<Upstream>>| async move {
+            client
+                .send(Request::new(Method::Get, "http://example.com/"))
+                .await
+        };
+
+        // Reaching 8 takes 1 + 2 + ... + 7 successes.
+        for _ in 0..28 {
+            send(client.clone()).await?;
+        }
+        assert_eq!(client.limit(), 8);
+        for _ in 0..10 {
+            send(client.clone()).await?;
+        }
+        assert_eq!(client.limit(), 8);
+
+        upstream.failing.store(true, Ordering::SeqCst);
+        for expected in &[4, 2, 1, 1] {
+            send(client.clone()).await?;
+            assert_eq!(client.limit(), *expected);
+        }
+
+        upstream.max.store(0, Ordering::SeqCst);
+        let tasks: Vec<_> = (0..4).map(|_| task::spawn(send(client.clone()))).collect();
+        for task in tasks {
+            task.await?;
+        }
+        assert_eq!(upstream.max.load(Ordering::SeqCst), 1);
+        assert_eq!(client.in_flight(), 0);
+        Ok(())
+    }
+}
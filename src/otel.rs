@@ -0,0 +1,283 @@
+//! Record OpenTelemetry metrics for every request.
+
+use crate::{Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use opentelemetry::metrics::{Counter, Histogram, Meter, UpDownCounter};
+use opentelemetry::KeyValue;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A client which records request metrics into an OpenTelemetry `Meter`, following the HTTP
+/// client semantic conventions:
+///
+/// - `http.client.requests`, a counter of completed requests.
+/// - `http.client.request.duration`, a histogram of the seconds until each response head
+///   arrived.
+/// - `http.client.active_requests`, the number of requests waiting for a response head. A
+///   request counts from when its future is first polled until the head arrives or the future
+///   is dropped.
+///
+/// Every metric is labelled with `http.request.method` and `server.address`. The counter and
+/// histogram are also labelled with `http.response.status_code`, or `error.type` for requests
+/// which failed without a response.
+///
+/// A meter from `opentelemetry::global::meter` before a meter provider is installed records
+/// nothing, without failing.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::otel::OtelClient;
+///
+/// let meter = opentelemetry::global::meter("my-app");
+/// let client = OtelClient::new(H1Client::new(), &meter);
+/// # }
+/// ```
+pub struct OtelClient<C> {
+    client: C,
+    instruments: Arc<Instruments>,
+}
+
+struct Instruments {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+    active: UpDownCounter<i64>,
+}
+
+impl<C: fmt::Debug> fmt::Debug for OtelClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtelClient")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+impl<C: HttpClient> OtelClient<C> {
+    /// Wrap `client`, recording metrics with instruments created from `meter`.
+    pub fn new(client: C, meter: &Meter) -> Self {
+        let instruments = Instruments {
+            requests: meter
+                .u64_counter("http.client.requests")
+                .with_description("Completed HTTP requests")
+                .build(),
+            duration: meter
+                .f64_histogram("http.client.request.duration")
+                .with_description("Time until the response head arrived")
+                .with_unit("s")
+                .build(),
+            active: meter
+                .i64_up_down_counter("http.client.active_requests")
+                .with_description("HTTP requests waiting for a response")
+                .build(),
+        };
+        Self {
+            client,
+            instruments: Arc::new(instruments),
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for OtelClient<C> {
+    fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", req.method().to_string()),
+            KeyValue::new(
+                "server.address",
+                req.url().host_str().unwrap_or_default().to_string(),
+            ),
+        ];
+        let instruments = self.instruments.clone();
+        let start = Instant::now();
+        let res = self.client.send(req);
+
+        Box::pin(async move {
+            let active = Active::start(instruments.clone(), attributes.clone());
+            let res = res.await;
+            let elapsed = start.elapsed().as_secs_f64();
+            drop(active);
+
+            match &res {
+                Ok(res) => attributes.push(KeyValue::new(
+                    "http.response.status_code",
+                    i64::from(u16::from(res.status())),
+                )),
+                Err(_) => attributes.push(KeyValue::new("error.type", "request_error")),
+            }
+            instruments.requests.add(1, &attributes);
+            instruments.duration.record(elapsed, &attributes);
+            res
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// Counts a request as active until dropped, so a request whose future is dropped before the
+/// response arrives doesn't stay counted.
+struct Active {
+    instruments: Arc<Instruments>,
+    attributes: Vec<KeyValue>,
+}
+
+impl Active {
+    fn start(instruments: Arc<Instruments>, attributes: Vec<KeyValue>) -> Self {
+        instruments.active.add(1, &attributes);
+        Self {
+            instruments,
+            attributes,
+        }
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        self.instruments.active.add(-1, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::{Method, StatusCode};
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::data::{self, ResourceMetrics};
+    use opentelemetry_sdk::metrics::reader::MetricReader;
+    use opentelemetry_sdk::metrics::{
+        InstrumentKind, ManualReader, MetricResult, Pipeline, SdkMeterProvider, Temporality,
+    };
+    use opentelemetry_sdk::Resource;
+    use std::sync::Weak;
+
+    /// A `ManualReader` which the test keeps a handle to after the provider takes it.
+    #[derive(Debug, Clone)]
+    struct SharedReader(Arc<ManualReader>);
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline)
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> MetricResult<()> {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> MetricResult<()> {
+            self.0.shutdown()
+        }
+
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Teapot;
+
+    impl HttpClient for Teapot {
+        fn send(&self, _req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            Box::pin(async { Ok(Response::new(StatusCode::ImATeapot)) })
+        }
+    }
+
+    #[derive(Debug)]
+    struct Hang;
+
+    impl HttpClient for Hang {
+        fn send(&self, _req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            Box::pin(futures::future::pending())
+        }
+    }
+
+    /// The current value of `http.client.active_requests`, if anything was recorded yet.
+    fn active_requests(reader: &SharedReader) -> Option<i64> {
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut metrics).unwrap();
+        let metric = metrics
+            .scope_metrics
+            .first()?
+            .metrics
+            .iter()
+            .find(|metric| metric.name == "http.client.active_requests")?;
+        let active = metric.data.as_any().downcast_ref::<data::Sum<i64>>()?;
+        Some(active.data_points.first()?.value)
+    }
+
+    #[async_std::test]
+    async fn counts_active_requests_while_polled() {
+        let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let client = OtelClient::new(Hang, &provider.meter("test"));
+
+        let mut res = client.send(Request::new(Method::Get, "http://example.com/"));
+        assert_eq!(active_requests(&reader), None);
+
+        assert!(futures::poll!(&mut res).is_pending());
+        assert_eq!(active_requests(&reader), Some(1));
+
+        drop(res);
+        assert_eq!(active_requests(&reader), Some(0));
+    }
+
+    #[async_std::test]
+    async fn records_request_metrics() -> Result<(), Error> {
+        let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let client = OtelClient::new(Teapot, &provider.meter("test"));
+
+        client
+            .send(Request::new(Method::Get, "http://example.com/"))
+            .await?;
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut metrics).unwrap();
+        let metrics = &metrics.scope_metrics[0].metrics;
+        let find = |name: &str| {
+            metrics
+                .iter()
+                .find(|metric| metric.name == name)
+                .unwrap()
+                .data
+                .as_any()
+        };
+
+        let requests = find("http.client.requests")
+            .downcast_ref::<data::Sum<u64>>()
+            .unwrap();
+        assert_eq!(requests.data_points[0].value, 1);
+        assert!(requests.data_points[0]
+            .attributes
+            .contains(&KeyValue::new("http.response.status_code", 418)));
+
+        let duration = find("http.client.request.duration")
+            .downcast_ref::<data::Histogram<f64>>()
+            .unwrap();
+        assert_eq!(duration.data_points[0].count, 1);
+
+        let active = find("http.client.active_requests")
+            .downcast_ref::<data::Sum<i64>>()
+            .unwrap();
+        assert_eq!(active.data_points[0].value, 0);
+        Ok(())
+    }
+}
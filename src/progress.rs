@@ -0,0 +1,218 @@
+//! Report upload and download progress.
+
+use crate::{Body, Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use futures::io::{AsyncBufRead, AsyncRead};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// A progress callback, called with the number of bytes transferred so far and the total if
+/// it's known.
+type Callback = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+/// A client which reports progress as request bodies are sent and response bodies are read.
+///
+/// Callbacks run on the task driving the transfer each time a chunk of the body moves, so they
+/// should return quickly, such as by updating a progress bar or sending on a channel, rather
+/// than blocking. The total is the body's length when it's known up front.
+///
+/// Upload progress counts the bytes the backend has taken from the request body, which can run
+/// ahead of what the server has received by the size of the backend's buffers.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::progress::ProgressClient;
+///
+/// let client = ProgressClient::new(H1Client::new()).on_download_progress(|read, total| {
+///     if let Some(total) = total {
+///         println!("{}%", read * 100 / total.max(1));
+///     }
+/// });
+/// # }
+/// ```
+pub struct ProgressClient<C> {
+    client: C,
+    upload: Option<Callback>,
+    download: Option<Callback>,
+}
+
+impl<C: fmt::Debug> fmt::Debug for ProgressClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressClient")
+            .field("client", &self.client)
+            .field("upload", &self.upload.is_some())
+            .field("download", &self.download.is_some())
+            .finish()
+    }
+}
+
+impl<C: HttpClient> ProgressClient<C> {
+    /// Wrap `client`, without any callbacks.
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            upload: None,
+            download: None,
+        }
+    }
+
+    /// Call `callback` as each request body is sent.
+    pub fn on_upload_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.upload = Some(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` as each response body is read.
+    pub fn on_download_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.download = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl<C: HttpClient> HttpClient for ProgressClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        if let Some(callback) = &self.upload {
+            let body = req.take_body();
+            req.set_body(Progress::wrap(body, callback.clone()));
+        }
+        let res = self.client.send(req);
+        let download = self.download.clone();
+        Box::pin(async move {
+            let mut res = res.await?;
+            if let Some(callback) = download {
+                let body = res.take_body();
+                res.set_body(Progress::wrap(body, callback));
+            }
+            Ok(res)
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// A body which reports how much of it has been read.
+struct Progress {
+    body: Body,
+    read: u64,
+    total: Option<u64>,
+    callback: Callback,
+}
+
+impl Progress {
+    fn wrap(body: Body, callback: Callback) -> Body {
+        let len = body.len();
+        let mime = body.mime().clone();
+        let progress = Self {
+            body,
+            read: 0,
+            total: len.map(|len| len as u64),
+            callback,
+        };
+        let mut body = Body::from_reader(progress, len);
+        body.set_mime(mime);
+        body
+    }
+
+    fn advance(&mut self, amt: usize) {
+        if amt > 0 {
+            self.read += amt as u64;
+            (self.callback)(self.read, self.total);
+        }
+    }
+}
+
+impl AsyncRead for Progress {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        self.advance(read);
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl AsyncBufRead for Progress {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().body).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.body).consume(amt);
+        self.advance(amt);
+    }
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use async_std::prelude::*;
+    use async_std::task;
+    use http_types::url::Url;
+    use http_types::Method;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[async_std::test]
+    async fn reports_full_size_when_done() -> http_types::Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").post(|mut r: tide::Request<()>| async move {
+            let uploaded = r.body_bytes().await?;
+            assert_eq!(uploaded.len(), 20_000);
+            let mut res = tide::Response::new(http_types::StatusCode::Ok);
+            res.set_body(vec![7u8; 100_000]);
+            Ok(res)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let uploads = Arc::new(Mutex::new(Vec::new()));
+            let downloads = Arc::new(Mutex::new(Vec::new()));
+            let (up, down) = (uploads.clone(), downloads.clone());
+            let client = ProgressClient::new(H1Client::new())
+                .on_upload_progress(move |sent, total| up.lock().unwrap().push((sent, total)))
+                .on_download_progress(move |read, total| down.lock().unwrap().push((read, total)));
+
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let mut req = Request::new(Method::Post, url);
+            req.set_body(vec![1; 20_000]);
+            let mut res = client.send(req).await?;
+            assert_eq!(res.body_bytes().await?.len(), 100_000);
+
+            let uploads = uploads.lock().unwrap();
+            assert_eq!(uploads.last(), Some(&(20_000, Some(20_000))));
+            let downloads = downloads.lock().unwrap();
+            assert!(downloads.len() > 1);
+            assert_eq!(downloads.last(), Some(&(100_000, Some(100_000))));
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,497 @@
+//! Serialize a struct into URL query parameters.
+
+use crate::{Error, Request};
+
+use http_types::StatusCode;
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt;
+
+/// Append the fields of `value` to the query string of `req`.
+///
+/// `value` must serialize as a struct or a map. Each field becomes one `key=value` pair, a
+/// sequence field becomes one pair per element with the key repeated, and `None` fields are
+/// skipped. Keys and values are percent-encoded as needed.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::query::append_query;
+/// use http_types::{Method, Request};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Search {
+///     q: &'static str,
+///     tag: Vec<&'static str>,
+/// }
+///
+/// let mut req = Request::new(Method::Get, "http://example.com/");
+/// append_query(&mut req, &Search { q: "http", tag: vec!["a", "b"] }).unwrap();
+/// assert_eq!(req.url().as_str(), "http://example.com/?q=http&tag=a&tag=b");
+/// ```
+pub fn append_query<T: Serialize + ?Sized>(req: &mut Request, value: &T) -> Result<(), Error> {
+    let mut pairs = Vec::new();
+    value
+        .serialize(QuerySerializer {
+            pairs: &mut pairs,
+            key: None,
+        })
+        .map_err(|err| Error::from_str(StatusCode::BadRequest, err.0))?;
+
+    if !pairs.is_empty() {
+        req.url_mut().query_pairs_mut().extend_pairs(pairs);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct QueryError(String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl ser::Error for QueryError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        QueryError(msg.to_string())
+    }
+}
+
+fn unsupported<T>(what: &str) -> Result<T, QueryError> {
+    Err(QueryError(format!(
+        "cannot serialize {} as query parameters",
+        what
+    )))
+}
+
+/// Serializes the top-level struct or map.
+struct QuerySerializer<'a> {
+    pairs: &'a mut Vec<(String, String)>,
+    key: Option<String>,
+}
+
+/// Serializes the value of a single field, under `key`.
+struct FieldSerializer<'a> {
+    key: &'a str,
+    pairs: &'a mut Vec<(String, String)>,
+}
+
+impl FieldSerializer<'_> {
+    fn push(self, value: impl ToString) -> Result<(), QueryError> {
+        self.pairs.push((self.key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+macro_rules! unsupported_top_level {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self $(, _: $arg)*) -> Result<Self::Ok, Self::Error> {
+                unsupported("a value that isn't a struct or map")
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for QuerySerializer<'a> {
+    type Ok = ();
+    type Error = QueryError;
+    type SerializeSeq = Impossible<(), QueryError>;
+    type SerializeTuple = Impossible<(), QueryError>;
+    type SerializeTupleStruct = Impossible<(), QueryError>;
+    type SerializeTupleVariant = Impossible<(), QueryError>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), QueryError>;
+
+    unsupported_top_level!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_none(self) -> Result<(), QueryError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), QueryError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), QueryError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), QueryError> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), QueryError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), QueryError> {
+        unsupported("an enum")
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, QueryError> {
+        unsupported("a sequence")
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, QueryError> {
+        unsupported("a tuple")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, QueryError> {
+        unsupported("a tuple struct")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, QueryError> {
+        unsupported("an enum")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, QueryError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, QueryError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, QueryError> {
+        unsupported("an enum")
+    }
+}
+
+impl<'a> ser::SerializeStruct for QuerySerializer<'a> {
+    type Ok = ();
+    type Error = QueryError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), QueryError> {
+        value.serialize(FieldSerializer {
+            key,
+            pairs: self.pairs,
+        })
+    }
+
+    fn end(self) -> Result<(), QueryError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for QuerySerializer<'a> {
+    type Ok = ();
+    type Error = QueryError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), QueryError> {
+        let mut pairs = Vec::new();
+        key.serialize(FieldSerializer {
+            key: "",
+            pairs: &mut pairs,
+        })?;
+        match (pairs.pop(), pairs.is_empty()) {
+            (Some((_, key)), true) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => unsupported("a map key that isn't a single value"),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), QueryError> {
+        // UNWRAP: serde always serializes a key before its value.
+        let key = self.key.take().unwrap();
+        value.serialize(FieldSerializer {
+            key: &key,
+            pairs: self.pairs,
+        })
+    }
+
+    fn end(self) -> Result<(), QueryError> {
+        Ok(())
+    }
+}
+
+macro_rules! push_display {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<(), QueryError> {
+                self.push(value)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = QueryError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), QueryError>;
+    type SerializeMap = Impossible<(), QueryError>;
+    type SerializeStruct = Impossible<(), QueryError>;
+    type SerializeStructVariant = Impossible<(), QueryError>;
+
+    push_display!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    );
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), QueryError> {
+        match std::str::from_utf8(value) {
+            Ok(value) => self.push(value),
+            Err(_) => unsupported("bytes that aren't UTF-8"),
+        }
+    }
+
+    fn serialize_none(self) -> Result<(), QueryError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), QueryError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), QueryError> {
+        self.push("")
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), QueryError> {
+        self.push("")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), QueryError> {
+        self.push(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), QueryError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), QueryError> {
+        unsupported("an enum with data")
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self, QueryError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, QueryError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, QueryError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, QueryError> {
+        unsupported("an enum with data")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, QueryError> {
+        unsupported("a nested map")
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, QueryError> {
+        unsupported("a nested struct")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, QueryError> {
+        unsupported("an enum with data")
+    }
+}
+
+macro_rules! serialize_elements {
+    ($($trait:ident::$method:ident),* $(,)?) => {
+        $(
+            impl<'a> ser::$trait for FieldSerializer<'a> {
+                type Ok = ();
+                type Error = QueryError;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), QueryError> {
+                    value.serialize(FieldSerializer {
+                        key: self.key,
+                        pairs: self.pairs,
+                    })
+                }
+
+                fn end(self) -> Result<(), QueryError> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+serialize_elements!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder;
+    use serde::ser::{SerializeStruct, Serializer};
+
+    struct Search {
+        q: String,
+        page: u32,
+        tag: Vec<String>,
+        lang: Option<String>,
+    }
+
+    // `#[derive(Serialize)]` is incompatible with the crate's `forbid(rust_2018_idioms)`.
+    impl Serialize for Search {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Search", 4)?;
+            s.serialize_field("q", &self.q)?;
+            s.serialize_field("page", &self.page)?;
+            s.serialize_field("tag", &self.tag)?;
+            s.serialize_field("lang", &self.lang)?;
+            s.end()
+        }
+    }
+
+    #[test]
+    fn repeats_keys_for_sequences() -> Result<(), Error> {
+        let search = Search {
+            q: "rust".to_string(),
+            page: 2,
+            tag: vec!["http".to_string(), "async".to_string()],
+            lang: None,
+        };
+        let req = builder::get("http://example.com/search")
+            .query_struct(&search)?
+            .build();
+
+        assert_eq!(
+            req.url().as_str(),
+            "http://example.com/search?q=rust&page=2&tag=http&tag=async"
+        );
+        let pairs: Vec<_> = req.url().query_pairs().into_owned().collect();
+        assert_eq!(pairs.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_reserved_characters() -> Result<(), Error> {
+        let search = Search {
+            q: "a&b=c d/é".to_string(),
+            page: 1,
+            tag: Vec::new(),
+            lang: Some("en".to_string()),
+        };
+        let req = builder::get("http://example.com/?x=1")
+            .query_struct(&search)?
+            .build();
+
+        assert_eq!(
+            req.url().as_str(),
+            "http://example.com/?x=1&q=a%26b%3Dc+d%2F%C3%A9&page=1&lang=en"
+        );
+        let q = req.url().query_pairs().find(|(k, _)| k == "q").unwrap().1;
+        assert_eq!(q, "a&b=c d/é");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_struct_values() {
+        let mut req = Request::new(http_types::Method::Get, "http://example.com/");
+        assert!(append_query(&mut req, &42).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Follow redirects.
+
+use crate::replay::{clone_request, take_body};
+use crate::{Body, Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use http_types::headers::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION,
+    TRANSFER_ENCODING,
+};
+use http_types::{Method, StatusCode};
+use std::sync::Arc;
+
+/// A client which follows redirects, up to 10 in a row by default.
+///
+/// The method is rewritten the way browsers and most servers expect:
+///
+/// - `303 See Other` turns every method but `HEAD` into `GET`, dropping the body.
+/// - `301 Moved Permanently` and `302 Found` turn `POST` into `GET`, dropping the body, and
+///   keep any other method.
+/// - `307 Temporary Redirect` and `308 Permanent Redirect` keep the method and body.
+///
+/// A relative `Location`, including a protocol-relative `//host/path`, is resolved against the
+/// URL of the request it answers.
+///
+/// Request bodies are read into memory before sending, so `307` and `308` redirects can repeat
+/// them. The `Authorization`, `Proxy-Authorization` and `Cookie` headers are dropped when a
+/// redirect leads to another origin, so credentials meant for one server never reach another.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::redirect::RedirectClient;
+///
+/// let client = RedirectClient::new(H1Client::new()).with_max_redirects(5);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RedirectClient<C> {
+    client: Arc<C>,
+    max_redirects: usize,
+    preserve_method: bool,
+}
+
+impl<C: HttpClient> RedirectClient<C> {
+    /// Wrap `client`.
+    pub fn new(client: C) -> Self {
+        Self {
+            client: Arc::new(client),
+            max_redirects: 10,
+            preserve_method: false,
+        }
+    }
+
+    /// Fail with a `502 Bad Gateway` error after following `max` redirects in a row.
+    pub fn with_max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Keep the method and body across every redirect, including `301`, `302` and `303`, for
+    /// servers which send `302` where they mean `307`.
+    ///
+    /// Disabled by default.
+    pub fn preserve_method(mut self, preserve: bool) -> Self {
+        self.preserve_method = preserve;
+        self
+    }
+}
+
+impl<C: HttpClient> HttpClient for RedirectClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let client = self.client.clone();
+        let max_redirects = self.max_redirects;
+        let preserve_method = self.preserve_method;
+        Box::pin(async move {
+            let mut body = take_body(&mut req).await?;
+            let mut redirects = 0;
+
+            loop {
+                let mut next = clone_request(&req);
+                if let Some(body) = &body {
+                    req.set_body(Body::from(body.clone()));
+                }
+                let res = client.send(req).await?;
+
+                let status: u16 = res.status().into();
+                let location = match res.header(LOCATION) {
+                    Some(location) if matches!(status, 301 | 302 | 303 | 307 | 308) => {
+                        location.last().as_str().to_string()
+                    }
+                    _ => return Ok(res),
+                };
+                if redirects == max_redirects {
+                    return Err(Error::from_str(
+                        StatusCode::BadGateway,
+                        format!("gave up after {} redirects", redirects),
+                    ));
+                }
+                redirects += 1;
+
+                let url = next.url().join(&location).map_err(|err| {
+                    Error::from_str(
+                        StatusCode::BadGateway,
+                        format!("invalid redirect location '{}': {}", location, err),
+                    )
+                })?;
+                log::trace!("following {} redirect to {}", status, url);
+
+                let to_get = match status {
+                    303 => next.method() != Method::Head,
+                    301 | 302 => next.method() == Method::Post,
+                    _ => false,
+                };
+                if to_get && !preserve_method {
+                    next = rewrite_method(&next, Method::Get);
+                    body = None;
+                }
+                if url.origin() != next.url().origin() {
+                    next.remove_header(AUTHORIZATION);
+                    next.remove_header(PROXY_AUTHORIZATION);
+                    next.remove_header(COOKIE);
+                }
+                *next.url_mut() = url;
+                req = next;
+            }
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// Copy `req` with `method` instead, without the headers describing its body.
+fn rewrite_method(req: &Request, method: Method) -> Request {
+    let mut rewritten = clone_request(req);
+    rewritten.set_method(method);
+    rewritten.remove_header(CONTENT_LENGTH);
+    rewritten.remove_header(CONTENT_TYPE);
+    rewritten.remove_header(TRANSFER_ENCODING);
+    rewritten
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use async_std::prelude::*;
+    use async_std::task;
+    use http_types::url::Url;
+    use std::time::Duration;
+
+    /// Answers requests to each URL in `redirects` with a `302` to its `Location`, and anything
+    /// else with a `200`, recording the requests it receives.
+    #[derive(Debug, Clone)]
+    struct Recorder {
+        redirects: &'static [(&'static str, &'static str)],
+        requests: Arc<std::sync::Mutex<Vec<Request>>>,
+    }
+
+    impl Recorder {
+        fn new(redirects: &'static [(&'static str, &'static str)]) -> Self {
+            Self {
+                redirects,
+                requests: Arc::default(),
+            }
+        }
+
+        /// Whether each request received carried an `Authorization` header, by URL.
+        fn authorized(&self) -> Vec<(String, bool)> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|req| (req.url().to_string(), req.header(AUTHORIZATION).is_some()))
+                .collect()
+        }
+    }
+
+    impl HttpClient for Recorder {
+        fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let location = self
+                .redirects
+                .iter()
+                .find(|(url, _)| *url == req.url().as_str())
+                .map(|(_, location)| *location);
+            self.requests.lock().unwrap().push(clone_request(&req));
+            Box::pin(async move {
+                let mut res = Response::new(StatusCode::Ok);
+                if let Some(location) = location {
+                    res.set_status(StatusCode::Found);
+                    res.insert_header(LOCATION, location);
+                }
+                Ok(res)
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn resolves_relative_locations() -> http_types::Result<()> {
+        let recorder = Recorder::new(&[
+            ("https://a.example/start", "//a.example/next"),
+            ("https://a.example/next", "/dir/file"),
+            ("https://a.example/dir/file", "http://a.example/dir/file"),
+            ("http://a.example/dir/file", "page"),
+        ]);
+        let client = RedirectClient::new(recorder.clone());
+
+        let mut req = Request::new(Method::Get, "https://a.example/start");
+        req.insert_header(AUTHORIZATION, "Basic dXNlcjpzZWNyZXQ=");
+        client.send(req).await?;
+
+        // Downgrading to http leaves the origin, so the credentials are dropped from then on.
+        let expected = [
+            ("https://a.example/start", true),
+            ("https://a.example/next", true),
+            ("https://a.example/dir/file", true),
+            ("http://a.example/dir/file", false),
+            ("http://a.example/dir/page", false),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(url, authorized)| (url.to_string(), *authorized))
+            .collect();
+        assert_eq!(recorder.authorized(), expected);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sets_bodies_only_when_kept() -> http_types::Result<()> {
+        let recorder = Recorder::new(&[
+            ("http://a.example/form", "/done"),
+            ("http://a.example/page", "/done"),
+        ]);
+        let client = RedirectClient::new(recorder.clone());
+
+        let mut post = Request::new(Method::Post, "http://a.example/form");
+        post.set_body("hello");
+        client.send(post).await?;
+        client
+            .send(Request::new(Method::Get, "http://a.example/page"))
+            .await?;
+
+        // Only the original `POST` has a body, so it's the only request with a type.
+        let requests = recorder.requests.lock().unwrap();
+        let types: Vec<_> = requests
+            .iter()
+            .map(|req| (req.method(), req.header(CONTENT_TYPE).is_some()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                (Method::Post, true),
+                (Method::Get, false),
+                (Method::Get, false),
+                (Method::Get, false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strips_credentials_across_origins() -> http_types::Result<()> {
+        let recorder = Recorder::new(&[
+            ("http://a.example/same", "/"),
+            ("http://a.example/", "http://b.example/"),
+        ]);
+        let client = RedirectClient::new(recorder.clone());
+
+        let mut req = Request::new(Method::Get, "http://a.example/same");
+        req.insert_header(AUTHORIZATION, "Basic dXNlcjpzZWNyZXQ=");
+        req.insert_header(PROXY_AUTHORIZATION, "Basic cHJveHk6c2VjcmV0");
+        req.insert_header(COOKIE, "session=1");
+        req.insert_header("x-trace", "1");
+        req.ext_mut().insert(crate::retry::Idempotent);
+        client.send(req).await?;
+
+        let requests = recorder.requests.lock().unwrap();
+        let urls: Vec<_> = requests.iter().map(|req| req.url().as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "http://a.example/same",
+                "http://a.example/",
+                "http://b.example/"
+            ]
+        );
+        for req in &requests[..2] {
+            assert!(req.header(AUTHORIZATION).is_some());
+            assert!(req.header(PROXY_AUTHORIZATION).is_some());
+            assert!(req.header(COOKIE).is_some());
+        }
+        let other = &requests[2];
+        assert!(other.header(AUTHORIZATION).is_none());
+        assert!(other.header(PROXY_AUTHORIZATION).is_none());
+        assert!(other.header(COOKIE).is_none());
+        assert_eq!(other["x-trace"], "1");
+        assert!(other.ext().get::<crate::retry::Idempotent>().is_some());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rewrites_methods_by_status() -> http_types::Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        for (path, status) in &[
+            ("/found", StatusCode::Found),
+            ("/see-other", StatusCode::SeeOther),
+            ("/temporary", StatusCode::TemporaryRedirect),
+        ] {
+            let status = *status;
+            app.at(path).post(move |_| async move {
+                Ok(tide::Response::new(status).set_header("location", "/echo"))
+            });
+        }
+        app.at("/echo").all(|mut r: tide::Request<()>| async move {
+            let body = r.body_string().await?;
+            Ok(format!("{} {}", r.method(), body))
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let post = |path: &str| {
+                let url = Url::parse(&format!("http://localhost:{}{}", port, path)).unwrap();
+                let mut req = Request::new(Method::Post, url);
+                req.set_body("hello");
+                req
+            };
+
+            let client = RedirectClient::new(H1Client::new());
+            let mut res = client.send(post("/see-other")).await?;
+            assert_eq!(res.body_string().await?, "GET ");
+            let mut res = client.send(post("/temporary")).await?;
+            assert_eq!(res.body_string().await?, "POST hello");
+            let mut res = client.send(post("/found")).await?;
+            assert_eq!(res.body_string().await?, "GET ");
+
+            let client = RedirectClient::new(H1Client::new()).preserve_method(true);
+            let mut res = client.send(post("/found")).await?;
+            assert_eq!(res.body_string().await?, "POST hello");
+
+            let client = RedirectClient::new(H1Client::new()).with_max_redirects(0);
+            let err = client.send(post("/found")).await.unwrap_err();
+            assert_eq!(err.status(), StatusCode::BadGateway);
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! Copy requests, so middleware can send them again.
+
+use crate::retry::Idempotent;
+use crate::{Error, Request};
+
+use http_types::headers::CONTENT_TYPE;
+
+/// Copy the method, URL, version, headers and extensions of `req`, without its body.
+///
+/// Extensions can't be cloned in general, so only the ones this crate defines are copied.
+pub(crate) fn clone_request(req: &Request) -> Request {
+    let mut clone = Request::new(req.method(), req.url().clone());
+    clone.set_version(req.version());
+    for (name, values) in req {
+        clone.append_header(name, values);
+    }
+    if req.ext().get::<Idempotent>().is_some() {
+        clone.ext_mut().insert(Idempotent);
+    }
+    clone
+}
+
+/// Read the body of `req` into memory, leaving it empty, or return `None` if it had no body.
+///
+/// Taking a body gives the request a `Content-Type` if it had none, and setting one again does
+/// the same, so bodyless requests are left without a body and without the header.
+pub(crate) async fn take_body(req: &mut Request) -> Result<Option<Vec<u8>>, Error> {
+    let had_type = req.header(CONTENT_TYPE).is_some();
+    let body = req.take_body().into_bytes().await?;
+    if !body.is_empty() {
+        return Ok(Some(body));
+    }
+    if !had_type {
+        req.remove_header(CONTENT_TYPE);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::Method;
+
+    #[test]
+    fn keeps_headers_and_extensions() {
+        let mut req = Request::new(Method::Post, "http://example.com/charge");
+        req.insert_header("x-token", "1");
+        req.ext_mut().insert(Idempotent);
+
+        let clone = clone_request(&req);
+        assert_eq!(clone.method(), Method::Post);
+        assert_eq!(clone.url(), req.url());
+        assert_eq!(clone["x-token"], "1");
+        assert_eq!(clone.ext().get::<Idempotent>(), Some(&Idempotent));
+    }
+}
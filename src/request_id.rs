@@ -0,0 +1,201 @@
+//! Tag every request with a unique id, and carry it in errors.
+
+use crate::{Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use http_types::headers::HeaderName;
+use std::fmt;
+
+/// The header request ids are sent in by default.
+const X_REQUEST_ID: &str = "x-request-id";
+
+/// A client which sets a unique id header on every request it sends, for correlating logs
+/// across services.
+///
+/// Each request gets a new random (version 4) UUID in the `X-Request-Id` header, unless it
+/// already has the header, in which case the caller's id is kept. Any error returned for the
+/// request carries the id as a [`RequestIdError`].
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::request_id::RequestIdClient;
+///
+/// let client = RequestIdClient::new(H1Client::new()).with_header("X-Trace-Id");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RequestIdClient<C> {
+    client: C,
+    header: HeaderName,
+}
+
+impl<C: HttpClient> RequestIdClient<C> {
+    /// Wrap `client`, using the `X-Request-Id` header.
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            header: HeaderName::from(X_REQUEST_ID),
+        }
+    }
+
+    /// Send ids in the `name` header instead of `X-Request-Id`.
+    pub fn with_header(mut self, name: impl Into<HeaderName>) -> Self {
+        self.header = name.into();
+        self
+    }
+}
+
+impl<C: HttpClient> HttpClient for RequestIdClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        let id = match req.header(&self.header) {
+            Some(values) => values.last().as_str().to_string(),
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                req.insert_header(&self.header, id.as_str());
+                id
+            }
+        };
+
+        let res = self.client.send(req);
+        Box::pin(async move {
+            res.await.map_err(|err| {
+                let status = err.status();
+                let context = RequestIdError {
+                    id,
+                    message: err.to_string(),
+                };
+                Error::new(status, err.into_inner().context(context))
+            })
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+/// The context attached to errors returned by a `RequestIdClient`, recording the id of the
+/// failed request.
+///
+/// The error keeps its status, and its message gains the id. Get the id back with
+/// `err.downcast_ref::<RequestIdError>()`.
+#[derive(Debug, Clone)]
+pub struct RequestIdError {
+    id: String,
+    message: String,
+}
+
+impl RequestIdError {
+    /// The id of the request which failed.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Display for RequestIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (request id {})", self.message, self.id)
+    }
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use async_std::prelude::*;
+    use async_std::task;
+    use http_types::url::Url;
+    use http_types::Method;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[async_std::test]
+    async fn sets_request_id_header() -> http_types::Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|r: tide::Request<()>| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body(r.header("x-request-id").unwrap()[0].as_str());
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let client = RequestIdClient::new(H1Client::new());
+
+            let mut res = client.send(Request::new(Method::Get, url.clone())).await?;
+            let id = res.body_string().await?;
+            assert!(uuid::Uuid::parse_str(&id).is_ok());
+
+            let mut req = Request::new(Method::Get, url);
+            req.insert_header("X-Request-Id", "mine");
+            let mut res = client.send(req).await?;
+            assert_eq!(res.body_string().await?, "mine");
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn errors_carry_request_id() {
+        let port = portpicker::pick_unused_port().unwrap();
+        let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+        let client = RequestIdClient::new(H1Client::new()).with_header("X-Trace-Id");
+
+        let mut req = Request::new(Method::Get, url);
+        req.insert_header("X-Trace-Id", "trace-1");
+        let err = client.send(req).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RequestIdError>().unwrap().id(),
+            "trace-1"
+        );
+        assert!(err.to_string().ends_with("(request id trace-1)"));
+    }
+
+    /// Fails every request, recording the `X-Request-Id` it was sent with.
+    #[derive(Debug, Clone, Default)]
+    struct Failing {
+        sent: Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    impl HttpClient for Failing {
+        fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let id = req.header(X_REQUEST_ID).map(|id| id.as_str().to_string());
+            *self.sent.lock().unwrap() = id;
+            Box::pin(async {
+                Err(Error::from_str(
+                    http_types::StatusCode::BadGateway,
+                    "connection reset",
+                ))
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn errors_carry_generated_request_id() {
+        let failing = Failing::default();
+        let client = RequestIdClient::new(failing.clone());
+
+        let err = client
+            .send(Request::new(Method::Get, "http://example.com/"))
+            .await
+            .unwrap_err();
+        let sent = failing.sent.lock().unwrap().clone().unwrap();
+        assert!(uuid::Uuid::parse_str(&sent).is_ok());
+        assert_eq!(err.downcast_ref::<RequestIdError>().unwrap().id(), sent);
+        assert_eq!(err.status(), http_types::StatusCode::BadGateway);
+    }
+}
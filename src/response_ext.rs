@@ -0,0 +1,169 @@
+//! Extension methods for `Response`.
+
+use crate::{Error, Response};
+
+use futures::future::BoxFuture;
+use http_types::url::Url;
+
+#[cfg(feature = "digest")]
+use crate::checksum::{DigestOutput, DigestReader};
+#[cfg(feature = "json")]
+use crate::json::JsonLines;
+#[cfg(feature = "digest")]
+use digest::Digest;
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+
+/// Extension methods for the `Response` returned by an `HttpClient`.
+pub trait ResponseExt: Sized {
+    /// Turn a `4xx` or `5xx` response to a request for `url` into an `Error`, passing any other
+    /// response through.
+    ///
+    /// The error carries the response's status code, so `Error::status` reports it, and its
+    /// message names `url` for logging. A `Response` doesn't record the URL it was received
+    /// from, so it must be passed in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http_client::{Response, ResponseExt};
+    /// use http_types::{StatusCode, Url};
+    ///
+    /// let url = Url::parse("http://example.com/missing").unwrap();
+    /// let err = Response::new(StatusCode::NotFound)
+    ///     .error_for_status(&url)
+    ///     .unwrap_err();
+    /// assert_eq!(err.status(), StatusCode::NotFound);
+    /// assert_eq!(err.to_string(), "404 Not Found for http://example.com/missing");
+    /// ```
+    fn error_for_status(self, url: &Url) -> Result<Response, Error>;
+
+    /// Read the body to the end and throw it away.
+    ///
+    /// Backends with a connection pool, such as isahc, only return a connection to the pool once
+    /// its response body has been read to the end. Dropping a response with an unread body closes
+    /// the connection instead, so call this rather than dropping a body you don't need when the
+    /// connection should be reused.
+    fn discard_body(self) -> BoxFuture<'static, Result<(), Error>>;
+
+    /// Read the body while feeding it into the digest `D`.
+    ///
+    /// Returns a reader over the body, and a future which resolves to the digest once the reader
+    /// has reached the end of the body.
+    #[cfg_attr(feature = "docs", doc(cfg(digest)))]
+    #[cfg(feature = "digest")]
+    fn with_digest<D: Digest>(self) -> (DigestReader<D>, DigestOutput<D>);
+
+    /// Read the body while checking it against an `expected` digest `D`.
+    ///
+    /// Reaching the end of the body fails with `io::ErrorKind::InvalidData` if the digest of the
+    /// body doesn't match `expected`.
+    #[cfg_attr(feature = "docs", doc(cfg(digest)))]
+    #[cfg(feature = "digest")]
+    fn verify_digest<D: Digest>(self, expected: impl Into<Vec<u8>>) -> DigestReader<D>;
+
+    /// Read the body as newline-delimited JSON, deserializing each line into a `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use futures::stream::StreamExt;
+    /// use http_client::{Response, ResponseExt};
+    /// use http_types::StatusCode;
+    ///
+    /// let mut res = Response::new(StatusCode::Ok);
+    /// res.set_body("1\n2\n3\n");
+    /// let values: Vec<_> = res.json_lines::<u32>().collect().await;
+    /// assert_eq!(values.len(), 3);
+    /// assert_eq!(values[2].as_ref().unwrap(), &3);
+    /// # })
+    /// ```
+    #[cfg_attr(feature = "docs", doc(cfg(json)))]
+    #[cfg(feature = "json")]
+    fn json_lines<T: DeserializeOwned>(self) -> JsonLines<T>;
+}
+
+impl ResponseExt for Response {
+    fn error_for_status(self, url: &Url) -> Result<Response, Error> {
+        let status = self.status();
+        if status.is_client_error() || status.is_server_error() {
+            Err(Error::from_str(
+                status,
+                format!("{} {} for {}", status, status.canonical_reason(), url),
+            ))
+        } else {
+            Ok(self)
+        }
+    }
+
+    fn discard_body(mut self) -> BoxFuture<'static, Result<(), Error>> {
+        let body = self.take_body();
+        Box::pin(async move {
+            futures::io::copy(body, &mut futures::io::sink()).await?;
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "digest")]
+    fn with_digest<D: Digest>(mut self) -> (DigestReader<D>, DigestOutput<D>) {
+        DigestReader::new(self.take_body())
+    }
+
+    #[cfg(feature = "digest")]
+    fn verify_digest<D: Digest>(mut self, expected: impl Into<Vec<u8>>) -> DigestReader<D> {
+        DigestReader::verify(self.take_body(), expected.into())
+    }
+
+    #[cfg(feature = "json")]
+    fn json_lines<T: DeserializeOwned>(mut self) -> JsonLines<T> {
+        JsonLines::new(self.take_body())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::StatusCode;
+
+    fn url() -> Url {
+        Url::parse("http://example.com/missing").unwrap()
+    }
+
+    #[test]
+    fn error_for_status_rejects_not_found() {
+        let err = Response::new(StatusCode::NotFound)
+            .error_for_status(&url())
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "404 Not Found for http://example.com/missing"
+        );
+    }
+
+    #[test]
+    fn error_for_status_passes_ok_through() {
+        let res = Response::new(StatusCode::Ok)
+            .error_for_status(&url())
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+
+    #[async_std::test]
+    async fn discard_body_reads_to_the_end() -> Result<(), Error> {
+        let (mut sender, body) = crate::body::channel();
+        let producer = async_std::task::spawn(async move {
+            for _ in 0..16 {
+                sender.send(vec![0; 1024]).await?;
+            }
+            http_types::Result::Ok(())
+        });
+
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(body);
+        res.discard_body().await?;
+        // The producer only finishes once every chunk has been read.
+        producer.await
+    }
+}
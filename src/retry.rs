@@ -0,0 +1,224 @@
+//! Retry requests which fail without a response.
+
+use crate::replay::{clone_request, take_body};
+use crate::{Body, Capabilities, Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use http_types::headers::HeaderName;
+use http_types::Method;
+use std::sync::Arc;
+
+/// The header which marks a request as safe to retry, unless disabled.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// A request extension marking a request as safe to retry whatever its method.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::retry::Idempotent;
+/// use http_types::{Method, Request};
+///
+/// let mut req = Request::new(Method::Post, "http://example.com/charge");
+/// req.ext_mut().insert(Idempotent);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Idempotent;
+
+/// A client which retries requests that fail with an error rather than a response, up to twice
+/// by default.
+///
+/// Only idempotent requests are retried, since sending any other request again could repeat
+/// its side effects. Those are requests with an idempotent method (`GET`, `HEAD`, `OPTIONS`,
+/// `TRACE`, `PUT` and `DELETE`), requests carrying the [`Idempotent`] extension, and requests
+/// with an `Idempotency-Key` header, unless
+/// [`retry_with_idempotency_key`](RetryClient::retry_with_idempotency_key) is disabled.
+///
+/// The bodies of retried requests are read into memory before sending, so they can be sent
+/// again. Responses are never retried, whatever their status.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::retry::RetryClient;
+///
+/// let client = RetryClient::new(H1Client::new()).with_max_retries(3);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RetryClient<C> {
+    client: Arc<C>,
+    max_retries: usize,
+    idempotency_key: bool,
+}
+
+impl<C: HttpClient> RetryClient<C> {
+    /// Wrap `client`.
+    pub fn new(client: C) -> Self {
+        Self {
+            client: Arc::new(client),
+            max_retries: 2,
+            idempotency_key: true,
+        }
+    }
+
+    /// Send each request at most `max` more times after it first fails.
+    pub fn with_max_retries(mut self, max: usize) -> Self {
+        self.max_retries = max;
+        self
+    }
+
+    /// Whether an `Idempotency-Key` header marks a request as safe to retry.
+    ///
+    /// Enabled by default.
+    pub fn retry_with_idempotency_key(mut self, enabled: bool) -> Self {
+        self.idempotency_key = enabled;
+        self
+    }
+
+    fn is_idempotent(&self, req: &Request) -> bool {
+        let method = matches!(
+            req.method(),
+            Method::Get
+                | Method::Head
+                | Method::Options
+                | Method::Trace
+                | Method::Put
+                | Method::Delete
+        );
+        method
+            || req.ext().get::<Idempotent>().is_some()
+            || (self.idempotency_key && req.header(HeaderName::from(IDEMPOTENCY_KEY)).is_some())
+    }
+}
+
+impl<C: HttpClient> HttpClient for RetryClient<C> {
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+        if self.max_retries == 0 || !self.is_idempotent(&req) {
+            return self.client.send(req);
+        }
+
+        let client = self.client.clone();
+        let max_retries = self.max_retries;
+        Box::pin(async move {
+            let body = take_body(&mut req).await?;
+            let mut attempt = 0;
+            loop {
+                let mut next = clone_request(&req);
+                if let Some(body) = &body {
+                    next.set_body(Body::from(body.clone()));
+                }
+                match client.send(next).await {
+                    Err(err) if attempt < max_retries => {
+                        attempt += 1;
+                        log::debug!("retrying {} after error: {}", req.url(), err);
+                    }
+                    res => return res,
+                }
+            }
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails the first request, then succeeds, counting attempts and checking each carries the
+    /// whole body.
+    #[derive(Debug, Clone, Default)]
+    struct Flaky {
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl HttpClient for Flaky {
+        fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                assert_eq!(req.body_string().await?, "payload");
+                if attempt == 0 {
+                    Err(Error::from_str(StatusCode::BadGateway, "connection reset"))
+                } else {
+                    Ok(Response::new(StatusCode::Ok))
+                }
+            })
+        }
+    }
+
+    async fn attempts(client: &RetryClient<Flaky>, flaky: &Flaky, mut req: Request) -> usize {
+        flaky.attempts.store(0, Ordering::SeqCst);
+        req.set_body("payload");
+        let _ = client.send(req).await;
+        flaky.attempts.load(Ordering::SeqCst)
+    }
+
+    #[async_std::test]
+    async fn retries_only_idempotent_requests() {
+        let flaky = Flaky::default();
+        let client = RetryClient::new(flaky.clone());
+        let url = "http://example.com/";
+
+        assert_eq!(
+            attempts(&client, &flaky, Request::new(Method::Get, url)).await,
+            2
+        );
+        assert_eq!(
+            attempts(&client, &flaky, Request::new(Method::Post, url)).await,
+            1
+        );
+
+        let mut req = Request::new(Method::Post, url);
+        req.ext_mut().insert(Idempotent);
+        assert_eq!(attempts(&client, &flaky, req).await, 2);
+
+        let mut req = Request::new(Method::Post, url);
+        req.insert_header("Idempotency-Key", "abc");
+        assert_eq!(attempts(&client, &flaky, req).await, 2);
+
+        let client = RetryClient::new(flaky.clone()).retry_with_idempotency_key(false);
+        let mut req = Request::new(Method::Post, url);
+        req.insert_header("Idempotency-Key", "abc");
+        assert_eq!(attempts(&client, &flaky, req).await, 1);
+    }
+
+    /// Fails the first request, then succeeds, checking each has no body or `Content-Type`.
+    #[derive(Debug, Clone, Default)]
+    struct FlakyBodyless {
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl HttpClient for FlakyBodyless {
+        fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Error>> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            assert!(req.header(http_types::headers::CONTENT_TYPE).is_none());
+            Box::pin(async move {
+                assert_eq!(req.body_string().await?, "");
+                if attempt == 0 {
+                    Err(Error::from_str(StatusCode::BadGateway, "connection reset"))
+                } else {
+                    Ok(Response::new(StatusCode::Ok))
+                }
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn retries_bodyless_requests_without_a_body() -> Result<(), Error> {
+        let flaky = FlakyBodyless::default();
+        let client = RetryClient::new(flaky.clone());
+        client
+            .send(Request::new(Method::Get, "http://example.com/"))
+            .await?;
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
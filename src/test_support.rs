@@ -0,0 +1,63 @@
+//! Build responses for testing code which handles them.
+
+use crate::Response;
+
+use futures::io::Cursor;
+use http_types::headers::{HeaderName, ToHeaderValues};
+use http_types::StatusCode;
+
+/// Build a response with a streaming body, wrapped the same way as the bodies backends return.
+///
+/// Every header is appended, so a name may be repeated. The body has a known length.
+///
+/// # Examples
+///
+/// ```
+/// # #[async_std::main]
+/// # async fn main() -> http_types::Result<()> {
+/// use http_client::test_support::response_from_parts;
+/// use http_types::StatusCode;
+///
+/// let mut res = response_from_parts(StatusCode::Ok, vec![("Content-Type", "text/plain")], "hi");
+/// assert_eq!(res.len(), Some(2));
+/// assert_eq!(res.body_string().await?, "hi");
+/// # Ok(()) }
+/// ```
+pub fn response_from_parts<N, V>(
+    status: StatusCode,
+    headers: impl IntoIterator<Item = (N, V)>,
+    body: impl Into<Vec<u8>>,
+) -> Response
+where
+    N: Into<HeaderName>,
+    V: ToHeaderValues,
+{
+    let mut res = Response::new(status);
+    for (name, values) in headers {
+        res.append_header(name, values);
+    }
+    let body = body.into();
+    let len = body.len();
+    res.set_body(crate::body::from_reader(Cursor::new(body), Some(len)));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn builds_streaming_response() -> http_types::Result<()> {
+        let mut res = response_from_parts(
+            StatusCode::Created,
+            vec![("X-Test", "1"), ("X-Test", "2")],
+            b"hello".to_vec(),
+        );
+        assert_eq!(res.status(), StatusCode::Created);
+        let values: Vec<_> = res["X-Test"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(values, ["1", "2"]);
+        assert_eq!(res.len(), Some(5));
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+}
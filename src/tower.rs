@@ -0,0 +1,113 @@
+//! Use an `HttpClient` as a [`tower`](https://docs.rs/tower) `Service`.
+
+use crate::{Error, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_service::Service;
+
+/// An adapter implementing `tower::Service` for any `HttpClient`, so it can be composed with
+/// tower layers such as timeouts, retries and concurrency limits.
+///
+/// The client is always ready, leaving backpressure to the layers wrapping it. Cloning the
+/// adapter is cheap, and clones share the client.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "h1_client")]
+/// # {
+/// use http_client::h1::H1Client;
+/// use http_client::tower::TowerClient;
+///
+/// let service = TowerClient::new(H1Client::new());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TowerClient<C> {
+    client: Arc<C>,
+}
+
+impl<C: HttpClient> TowerClient<C> {
+    /// Wrap `client`.
+    pub fn new(client: C) -> Self {
+        Self {
+            client: Arc::new(client),
+        }
+    }
+
+    /// The wrapped client.
+    pub fn get_ref(&self) -> &C {
+        &self.client
+    }
+}
+
+impl<C> Clone for TowerClient<C> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+        }
+    }
+}
+
+impl<C: HttpClient> Service<Request> for TowerClient<C> {
+    type Response = Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Response, Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        self.client.send(req)
+    }
+}
+
+#[cfg(all(test, feature = "h1_client"))]
+mod tests {
+    use super::*;
+    use crate::h1::H1Client;
+    use async_std::prelude::*;
+    use async_std::task;
+    use http_types::url::Url;
+    use http_types::Method;
+    use std::time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+
+    #[async_std::test]
+    async fn sends_through_tower_layers() -> http_types::Result<()> {
+        let port = portpicker::pick_unused_port().unwrap();
+        let mut app = tide::new();
+        app.at("/").get(|_| async move {
+            let mut response = tide::Response::new(http_types::StatusCode::Ok);
+            response.set_body("hello");
+            Ok(response)
+        });
+
+        let server = task::spawn(async move {
+            app.listen(("localhost", port)).await?;
+            http_types::Result::Ok(())
+        });
+
+        let client = task::spawn(async move {
+            task::sleep(Duration::from_millis(100)).await;
+            let url = Url::parse(&format!("http://localhost:{}/", port)).unwrap();
+            let mut service = ServiceBuilder::new()
+                .concurrency_limit(1)
+                .service(TowerClient::new(H1Client::new()));
+
+            for _ in 0..3 {
+                let req = Request::new(Method::Get, url.clone());
+                let mut res = service.ready().await?.call(req).await?;
+                assert_eq!(res.body_string().await?, "hello");
+            }
+            Ok(())
+        });
+
+        server.race(client).await?;
+
+        Ok(())
+    }
+}
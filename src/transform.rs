@@ -0,0 +1,46 @@
+//! Post-process response bodies before they're returned.
+
+use crate::{Body, Response};
+
+use http_types::headers::CONTENT_TYPE;
+
+/// A transformation applied to every response body a client returns.
+///
+/// Clients apply the transform after any decoding the backend performs itself, such as the
+/// automatic decompression done by `IsahcClient`, so `wrap` always sees the decoded body.
+///
+/// # Examples
+///
+/// ```
+/// use http_client::transform::BodyTransform;
+/// use http_client::Body;
+///
+/// /// Replace the body of every `text/plain` response.
+/// #[derive(Debug)]
+/// struct Redact;
+///
+/// impl BodyTransform for Redact {
+///     fn wrap(&self, content_type: Option<&str>, body: Body) -> Body {
+///         match content_type {
+///             Some(ct) if ct.starts_with("text/plain") => Body::from_string("redacted".into()),
+///             _ => body,
+///         }
+///     }
+/// }
+/// ```
+pub trait BodyTransform: std::fmt::Debug + Send + Sync + 'static {
+    /// Wrap a response `body`, given the response's `Content-Type` if it has one.
+    fn wrap(&self, content_type: Option<&str>, body: Body) -> Body;
+}
+
+/// Replace the body of `res` with its transformed body.
+// Unused when no backend is enabled.
+#[allow(dead_code)]
+pub(crate) fn apply(transform: &dyn BodyTransform, res: &mut Response) {
+    let body = res.take_body();
+    let content_type = res
+        .header(CONTENT_TYPE)
+        .map(|values| values.last().as_str());
+    let body = transform.wrap(content_type, body);
+    res.set_body(body);
+}